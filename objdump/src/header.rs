@@ -1,4 +1,3 @@
-use std::fs;
 use std::fs::File;
 use std::io::{Read, SeekFrom, Seek};
 
@@ -11,6 +10,11 @@ const ELF_SHENT_SIZE: u16 = 0x40;
 const ELF_PHENT_SIZE: u16 = 0x38;
 pub(crate) const ELF_SYMBOL_SIZE: u64 = 0x18;
 
+pub const PT_LOAD: u32 = 1;
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
 #[derive(Default)]
 pub struct ElfHeader {
     pub entry: u64,
@@ -31,6 +35,8 @@ pub struct SectionEntry {
 
 #[derive(Default)]
 pub struct ProgramEntry {
+    pub ptype: u32,
+    pub flags: u32,
     pub off: u64,
     pub vaddr: u64,
     pub filesz: u64,
@@ -54,7 +60,7 @@ impl ElfHeader {
         let mut rv: ElfHeader = Default::default();
         let mut b = [0; ELF_HEADER_SIZE as usize];
         reader.read_exact(&mut b)?;
-        if &b[0..4] != ELF_MAGIC {
+        if b[0..4] != ELF_MAGIC {
             return Err(Error::InvalidMagic);
         }
         if b[4] != 2 {
@@ -103,6 +109,8 @@ impl ProgramEntry {
         let mut b = [0; ELF_PHENT_SIZE as usize];
         reader.read_exact(&mut b)?;
 
+        rv.ptype = array_as_u32(&b[0x0..]);
+        rv.flags = array_as_u32(&b[0x04..]);
         rv.vaddr = array_as_u64(&b[0x10..]);
         rv.off = array_as_u64(&b[0x08..]);
         rv.filesz = array_as_u64(&b[0x20..]);
@@ -126,7 +134,7 @@ impl StringTable {
         match String::from_utf8(Vec::from(b)) {
             Ok(rv) =>
                 if rv.is_empty() {String::from("<null>")} else {rv},
-            Err(e) => String::from("<unknown>"),
+            Err(_) => String::from("<unknown>"),
         }
     }
 
@@ -139,12 +147,12 @@ impl StringTable {
             table: Vec::new(),
         };
         rv.table.resize(size, 0);
-        reader.read_exact(rv.table.as_mut_slice());
+        let _ = reader.read_exact(rv.table.as_mut_slice());
         rv
     }
 
     pub fn from_file(f: &mut File, sec: &SectionEntry) -> Self {
-        f.seek(SeekFrom::Start(sec.offset));
+        let _ = f.seek(SeekFrom::Start(sec.offset));
         StringTable::from_reader(f, sec.size as usize)
     }
 
@@ -171,6 +179,11 @@ impl SymbolEntry {
 #[test]
 fn test_elf_header() {
     let filename = "test_obj/a.out";
-    let mut f = fs::File::open(filename).unwrap();
+    let mut f = File::open(filename).unwrap();
     let header: ElfHeader = ElfHeader::from_reader(&mut f).unwrap();
+    assert_eq!(header.phnum, 2);
+    let _ = f.seek(SeekFrom::Start(header.phoff)).unwrap();
+    let text = ProgramEntry::from_reader(&mut f).unwrap();
+    assert_eq!(text.ptype, PT_LOAD);
+    assert_eq!(text.flags, PF_R | PF_X);
 }
\ No newline at end of file
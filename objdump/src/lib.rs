@@ -4,6 +4,7 @@ use std::fs::File;
 use std::io::{Seek, SeekFrom};
 
 pub use error::Error;
+pub use header::{PT_LOAD, PF_X, PF_W, PF_R};
 use header::{
     ElfHeader,
     ProgramEntry,
@@ -31,9 +32,9 @@ pub struct Elf {
 
 impl Elf {
     fn find_section(&self, name: &str) -> Result<&SectionEntry, Error> {
-        match self.sections.iter().filter(|x| {
+        match self.sections.iter().find(|x| {
             self.shstrtab.section_is(x, name)
-        }).next() {
+        }) {
             Some(rv) => Ok(rv),
             None => Err(Error::CanNotFindSection(String::from(name)))
         }
@@ -1,17 +1,35 @@
 import csv
+import re
+
+# formats that only differ in how they are matched share the operands
+dict_decode = {
+	"H": "I",
+	"HW": "I",
+	"A": "R",
+	"FU": "F",
+	"FX": "R",
+}
+
+def operands(t):
+	return dict_decode.get(t, t)
 
 
 print(r"""
-/** DONT EDIT THIS FILE **/
+/* DONT EDIT THIS FILE */
 /* This file is automatically generated.
   It is not intended for manual editing  */
 
+#![allow(clippy::upper_case_acronyms)]
+
 use crate::simulator::Simulator;
+use crate::mmu::Mmu;
 use crate::instruction::*;
 use crate::instruction::InstFormat::*;
-use crate::{main, register};
 use crate::register::{Reg};
 use crate::cache::CacheOp;
+use crate::trap::Exception;
+use crate::fpu;
+use crate::device;
 use Instruction::*;
 
 #[derive(Default, Copy, Clone)]
@@ -19,9 +37,13 @@ pub struct ExecuteInfo {
     pub exe_cycles: u64,
     pub mem_access: u64,
     pub load_reg: Reg,
-    pub reg_read: [Reg; 2],
+    pub reg_write: Reg,
+    pub reg_read: [Reg; 3],
     pub is_branch: bool,
     pub taken_branch: bool,
+    pub is_jump: bool,
+    // where a branch or jump goes when taken
+    pub target: u64,
 }
 
 #[derive(Debug)]
@@ -31,23 +53,74 @@ pub enum Instruction {
 with open('action.csv', 'r') as csvfile:
 	reader = csv.DictReader(csvfile, delimiter=',', quotechar='"')
 	for i in reader:
-		print('    {:<7}({}Operands),'.format(i["Name"], i["Type"]))
+		print('    {:<7}({}Operands),'.format(i["Name"], operands(i["Type"])))
 		
 
 print(r"""
 }
 
-pub(crate) fn execute(sim: &mut Simulator, inst: Instruction) -> ExecuteInfo {
+fn raise(e: Exception) -> Result<(), Exception> {
+    Err(e)
+}
+
+fn jump(pc: &mut u64, target: u64) -> Result<(), Exception> {
+    // with compressed instructions, targets only need to be 2-byte aligned
+    if target & 0b1 != 0 {
+        return Err(Exception::InstructionAddressMisaligned(target));
+    }
+    *pc = target;
+    Ok(())
+}
+
+// LR/SC and AMOs must be naturally aligned
+fn aligned(address: u64, size: u64, e: fn(u64) -> Exception) -> Result<u64, Exception> {
+    if address & (size - 1) != 0 {
+        return Err(e(address));
+    }
+    Ok(address)
+}
+
+// AMOs report every fault as a store/AMO fault
+fn store_fault(e: Exception) -> Exception {
+    match e {
+        Exception::LoadAccessFault(x) => Exception::StoreAccessFault(x),
+        Exception::LoadPageFault(x) => Exception::StorePageFault(x),
+        e => e,
+    }
+}
+
+fn amo_u32(m: &mut Mmu, address: u64, op: impl FnOnce(u32) -> u32) -> Result<u32, Exception> {
+    let t = m.load_u32(address).map_err(store_fault)?;
+    m.store_u32(address, op(t))?;
+    Ok(t)
+}
+
+fn amo_u64(m: &mut Mmu, address: u64, op: impl FnOnce(u64) -> u64) -> Result<u64, Exception> {
+    let t = m.load_u64(address).map_err(store_fault)?;
+    m.store_u64(address, op(t))?;
+    Ok(t)
+}
+
+// `len` is the size of the instruction in bytes, as fetched
+pub(crate) fn execute(sim: &mut Simulator, inst: Instruction, len: u64) -> Result<ExecuteInfo, Exception> {
     let r = &mut sim.regs;
-    let m = &mut sim.memory;
+    let m = &mut Mmu::new(&mut sim.memory, sim.csr.translation());
     let pc = &mut sim.pc;
-    let mut exe_cycles = 1;
+    let c = &mut sim.csr;
+    let s = &sim.stat;
+    let lr = &mut sim.reservation;
+    let f = &mut sim.fregs;
+    let exe_cycles;
     let mut access = 0;
     let mut load_reg = Default::default();
-    let mut reg_read: [Reg; 2] = Default::default();
+    let mut reg_write = Default::default();
+    let mut reg_read: [Reg; 3] = Default::default();
     let mut is_branch = false;
     let mut taken_branch = false;
+    let mut is_jump = false;
+    let mut target = 0;
     let mut access_op = CacheOp::Read;
+    let mut rmw = false;
     match inst {
 """)
 
@@ -58,59 +131,100 @@ dict_operands = {
 	"B": "imm, rs2, rs1",
 	"U": "imm, rd",
 	"J": "imm, rd",
+	"E": "",
+	"F": "rm, rs2, rs1, rd",
+	"R4": "rm, rs3, rs2, rs1, rd",
 	"": "",
 }
 
-dict_read_reg = {
-	"R": "rs2 rs1",
-	"I": "rs1",
-	"S": "rs2 rs1",
-	"B": "rs2 rs1",
-	"U": "",
-	"J": "",
-}
+# the operands an action reads or writes through the register files, FP
+# registers offset so that timing tells f10 from a0
+def accessed(action, op, names):
+	regs = []
+	for reg in names:
+		if re.search(r'\bf\.{}\w*\({}\b'.format(op, reg), action):
+			regs.append(reg + '.fp()')
+		elif re.search(r'\br\.{}\({}\b'.format(op, reg), action):
+			regs.append(reg)
+	return regs
 
 with open('action.csv', 'r') as csvfile:
 	reader = csv.DictReader(csvfile, delimiter=',', quotechar='"')
 	for i in reader:
+		action = i["Action1"] + ";" + i["Action2"]
+		reg_read = accessed(action, "get", ["rs1", "rs2", "rs3"])
+		reg_write = accessed(action, "set", ["rd"])
+		# only bind the operands the arm uses
+		fields = dict_operands[operands(i["Type"])].split(", ")
+		used = [x for x in fields if x and re.search(r'\b{}\b'.format(x), action)]
+		if len(used) < len([x for x in fields if x]):
+			used.append("..")
 		print('        {}({}Operands{{{}}}) => {{'.format(
-			i["Name"], i["Type"], dict_operands[i["Type"]]))
-		print('            {};'.format(i["Action1"]))
-		print('            {};'.format(i["Action2"]))
+			i["Name"], operands(i["Type"]), ", ".join(used)))
+		for action in (i["Action1"], i["Action2"]):
+			if action:
+				print('            {};'.format(action.rstrip(';')))
 		print('            exe_cycles = {};'.format(i["Cycles"]))
-		if i["CacheOp"]:
+		if i["CacheOp"] == "ReadWrite":
+			print('            rmw = true;')
+		elif i["CacheOp"]:
 			print('            access_op = CacheOp::{};'.format(i["CacheOp"]));
 
-		read_reg = dict_read_reg[i["Type"]].split()
-		for i, reg in enumerate(read_reg):
-			print('            reg_read[{}] = {};'.format(i, reg))
+		for n, reg in enumerate(reg_read):
+			print('            reg_read[{}] = {};'.format(n, reg))
+		for reg in reg_write:
+			print('            reg_write = {};'.format(reg))
 
 		print('        },')
 
 print(r"""
     };
-    let mem_access = if access == 0 { 0 } else { sim.cache.access(access, access_op) };
-    ExecuteInfo {
+    // a DTLB miss walks the page table through the caches
+    let mut walk = 0;
+    for x in &m.walks {
+        walk += sim.dtlb.access(x, &mut sim.cache);
+    }
+    let access = m.paddr.unwrap_or(access);
+    let mem_access = walk + match access {
+        0 => 0,
+        _ if sim.memory.is_device(access) => device::LATENCY,
+        // an AMO reads the line and then writes it back
+        _ if rmw => sim.cache.access(access, CacheOp::Read)
+            + sim.cache.access(access, CacheOp::Write),
+        _ => sim.cache.access(access, access_op),
+    };
+    Ok(ExecuteInfo {
         exe_cycles,
         mem_access,
         load_reg,
+        reg_write,
         reg_read,
         is_branch,
         taken_branch,
-    }
+        is_jump,
+        target,
+    })
 }
 
-pub(crate) fn matching<T>(code: T) -> Instruction
+pub(crate) fn matching<T>(code: T) -> Option<Instruction>
     where T: InstrMatch {
 """)
 
 dict_t = {
 	"R": "Funct7 Funct3 opcode",
 	"I": "Funct3 opcode",
+	"H": "Funct7 Funct3 opcode",
+	"HW": "Funct7 Funct3 opcode",
+	"A": "Funct7 Funct3 opcode",
 	"S": "Funct3 opcode",
 	"B": "Funct3 opcode",
 	"U": "opcode",
 	"J": "opcode",
+	"E": "Funct12 Funct3 opcode",
+	"F": "Funct7 opcode",
+	"FU": "Funct7 Rs2 opcode",
+	"FX": "Funct7 Rs2 Funct3 opcode",
+	"R4": "Funct7 opcode",
 	"": "",
 }
 
@@ -120,11 +234,11 @@ with open('action.csv', 'r') as csvfile:
 		t = i["Type"]
 		s = ','.join(map(lambda x: "0b_" + i[x], dict_t[t].split()))
 
-		print('    if code.is_match({}Format({})) {{ return {}(code.decode_{}()) }}'
-			.format(t, s, i["Name"], t))
+		print('    if code.is_match({}Format({})) {{ return Some({}(code.decode_{}())) }}'
+			.format(t, s, i["Name"], operands(t)))
 
 print(r"""
-    panic!("unknown instruction");
+    None
 }
 """)
 
@@ -1,14 +1,19 @@
 
-/** DONT EDIT THIS FILE **/
+/* DONT EDIT THIS FILE */
 /* This file is automatically generated.
   It is not intended for manual editing  */
 
+#![allow(clippy::upper_case_acronyms)]
+
 use crate::simulator::Simulator;
+use crate::mmu::Mmu;
 use crate::instruction::*;
 use crate::instruction::InstFormat::*;
-use crate::{main, register};
 use crate::register::{Reg};
 use crate::cache::CacheOp;
+use crate::trap::Exception;
+use crate::fpu;
+use crate::device;
 use Instruction::*;
 
 #[derive(Default, Copy, Clone)]
@@ -16,9 +21,13 @@ pub struct ExecuteInfo {
     pub exe_cycles: u64,
     pub mem_access: u64,
     pub load_reg: Reg,
-    pub reg_read: [Reg; 2],
+    pub reg_write: Reg,
+    pub reg_read: [Reg; 3],
     pub is_branch: bool,
     pub taken_branch: bool,
+    pub is_jump: bool,
+    // where a branch or jump goes when taken
+    pub target: u64,
 }
 
 #[derive(Debug)]
@@ -86,519 +95,1583 @@ pub enum Instruction {
     DIVUW  (ROperands),
     REMW   (ROperands),
     REMUW  (ROperands),
+    FENCE  (IOperands),
+    FENCEI (IOperands),
+    ECALL  (EOperands),
+    EBREAK (EOperands),
+    CSRRW  (IOperands),
+    CSRRS  (IOperands),
+    CSRRC  (IOperands),
+    CSRRWI (IOperands),
+    CSRRSI (IOperands),
+    CSRRCI (IOperands),
+    MRET   (EOperands),
+    SRET   (EOperands),
+    WFI    (EOperands),
+    SFENCEVMA(ROperands),
+    LRW    (ROperands),
+    SCW    (ROperands),
+    AMOSWAPW(ROperands),
+    AMOADDW(ROperands),
+    AMOXORW(ROperands),
+    AMOANDW(ROperands),
+    AMOORW (ROperands),
+    AMOMINW(ROperands),
+    AMOMAXW(ROperands),
+    AMOMINUW(ROperands),
+    AMOMAXUW(ROperands),
+    LRD    (ROperands),
+    SCD    (ROperands),
+    AMOSWAPD(ROperands),
+    AMOADDD(ROperands),
+    AMOXORD(ROperands),
+    AMOANDD(ROperands),
+    AMOORD (ROperands),
+    AMOMIND(ROperands),
+    AMOMAXD(ROperands),
+    AMOMINUD(ROperands),
+    AMOMAXUD(ROperands),
+    FLW    (IOperands),
+    FSW    (SOperands),
+    FMADDS (R4Operands),
+    FMSUBS (R4Operands),
+    FNMSUBS(R4Operands),
+    FNMADDS(R4Operands),
+    FADDS  (FOperands),
+    FSUBS  (FOperands),
+    FMULS  (FOperands),
+    FDIVS  (FOperands),
+    FSQRTS (FOperands),
+    FSGNJS (ROperands),
+    FSGNJNS(ROperands),
+    FSGNJXS(ROperands),
+    FMINS  (ROperands),
+    FMAXS  (ROperands),
+    FCVTSD (FOperands),
+    FEQS   (ROperands),
+    FLTS   (ROperands),
+    FLES   (ROperands),
+    FCLASSS(ROperands),
+    FCVTWS (FOperands),
+    FCVTWUS(FOperands),
+    FCVTLS (FOperands),
+    FCVTLUS(FOperands),
+    FCVTSW (FOperands),
+    FCVTSWU(FOperands),
+    FCVTSL (FOperands),
+    FCVTSLU(FOperands),
+    FMVXW  (ROperands),
+    FMVWX  (ROperands),
+    FLD    (IOperands),
+    FSD    (SOperands),
+    FMADDD (R4Operands),
+    FMSUBD (R4Operands),
+    FNMSUBD(R4Operands),
+    FNMADDD(R4Operands),
+    FADDD  (FOperands),
+    FSUBD  (FOperands),
+    FMULD  (FOperands),
+    FDIVD  (FOperands),
+    FSQRTD (FOperands),
+    FSGNJD (ROperands),
+    FSGNJND(ROperands),
+    FSGNJXD(ROperands),
+    FMIND  (ROperands),
+    FMAXD  (ROperands),
+    FCVTDS (FOperands),
+    FEQD   (ROperands),
+    FLTD   (ROperands),
+    FLED   (ROperands),
+    FCLASSD(ROperands),
+    FCVTWD (FOperands),
+    FCVTWUD(FOperands),
+    FCVTLD (FOperands),
+    FCVTLUD(FOperands),
+    FCVTDW (FOperands),
+    FCVTDWU(FOperands),
+    FCVTDL (FOperands),
+    FCVTDLU(FOperands),
+    FMVXD  (ROperands),
+    FMVDX  (ROperands),
+
+}
+
+fn raise(e: Exception) -> Result<(), Exception> {
+    Err(e)
+}
 
+fn jump(pc: &mut u64, target: u64) -> Result<(), Exception> {
+    // with compressed instructions, targets only need to be 2-byte aligned
+    if target & 0b1 != 0 {
+        return Err(Exception::InstructionAddressMisaligned(target));
+    }
+    *pc = target;
+    Ok(())
+}
+
+// LR/SC and AMOs must be naturally aligned
+fn aligned(address: u64, size: u64, e: fn(u64) -> Exception) -> Result<u64, Exception> {
+    if address & (size - 1) != 0 {
+        return Err(e(address));
+    }
+    Ok(address)
 }
 
-pub(crate) fn execute(sim: &mut Simulator, inst: Instruction) -> ExecuteInfo {
+// AMOs report every fault as a store/AMO fault
+fn store_fault(e: Exception) -> Exception {
+    match e {
+        Exception::LoadAccessFault(x) => Exception::StoreAccessFault(x),
+        Exception::LoadPageFault(x) => Exception::StorePageFault(x),
+        e => e,
+    }
+}
+
+fn amo_u32(m: &mut Mmu, address: u64, op: impl FnOnce(u32) -> u32) -> Result<u32, Exception> {
+    let t = m.load_u32(address).map_err(store_fault)?;
+    m.store_u32(address, op(t))?;
+    Ok(t)
+}
+
+fn amo_u64(m: &mut Mmu, address: u64, op: impl FnOnce(u64) -> u64) -> Result<u64, Exception> {
+    let t = m.load_u64(address).map_err(store_fault)?;
+    m.store_u64(address, op(t))?;
+    Ok(t)
+}
+
+// `len` is the size of the instruction in bytes, as fetched
+pub(crate) fn execute(sim: &mut Simulator, inst: Instruction, len: u64) -> Result<ExecuteInfo, Exception> {
     let r = &mut sim.regs;
-    let m = &mut sim.memory;
+    let m = &mut Mmu::new(&mut sim.memory, sim.csr.translation());
     let pc = &mut sim.pc;
-    let mut exe_cycles = 1;
+    let c = &mut sim.csr;
+    let s = &sim.stat;
+    let lr = &mut sim.reservation;
+    let f = &mut sim.fregs;
+    let exe_cycles;
     let mut access = 0;
     let mut load_reg = Default::default();
-    let mut reg_read: [Reg; 2] = Default::default();
+    let mut reg_write = Default::default();
+    let mut reg_read: [Reg; 3] = Default::default();
     let mut is_branch = false;
     let mut taken_branch = false;
+    let mut is_jump = false;
+    let mut target = 0;
     let mut access_op = CacheOp::Read;
+    let mut rmw = false;
     match inst {
 
         LUI(UOperands{imm, rd}) => {
             r.set(rd, imm);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
+            reg_write = rd;
         },
         AUIPC(UOperands{imm, rd}) => {
-            r.set(rd, *pc + imm);
-            *pc += 4;
+            r.set(rd, pc.wrapping_add(imm));
+            *pc += len;
             exe_cycles = 1;
+            reg_write = rd;
         },
         JAL(JOperands{imm, rd}) => {
-            let t = *pc + 4; *pc += imm; r.set(rd, t);;
-            ;
+            let t = *pc + len; jump(pc, pc.wrapping_add(imm))?; r.set(rd, t);
+            is_jump = true; target = *pc;
             exe_cycles = 1;
+            reg_write = rd;
         },
         JALR(IOperands{imm, rs1, rd}) => {
-            let t = *pc + 4; *pc = (r.get(rs1) + imm) & !1; r.set(rd, t);
-            ;
+            let t = *pc + len; jump(pc, r.get(rs1).wrapping_add(imm) & !1)?; r.set(rd, t);
+            is_jump = true; target = *pc;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         BEQ(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = r.get(rs1) == r.get(rs2);
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = r.get(rs1) == r.get(rs2);
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         BNE(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = r.get(rs1) != r.get(rs2);
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = r.get(rs1) != r.get(rs2);
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         BLT(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = (r.get(rs1) as i64) < (r.get(rs2) as i64);
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = (r.get(rs1) as i64) < (r.get(rs2) as i64);
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         BGE(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = r.get(rs1) as i64 >= r.get(rs2) as i64;
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = r.get(rs1) as i64 >= r.get(rs2) as i64;
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         BLTU(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = r.get(rs1) < r.get(rs2);
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = r.get(rs1) < r.get(rs2);
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         BGEU(BOperands{imm, rs2, rs1}) => {
-            is_branch = true; taken_branch = r.get(rs1) >= r.get(rs2);
-            if taken_branch { *pc += imm } else {*pc += 4};
+            is_branch = true; target = pc.wrapping_add(imm); taken_branch = r.get(rs1) >= r.get(rs2);
+            if taken_branch { jump(pc, pc.wrapping_add(imm))? } else { *pc += len };
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         LB(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u8(access) as i8 as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u8(access)? as i8 as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         LH(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u16(access) as i16 as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u16(access)? as i16 as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         LW(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u32(access) as i32 as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u32(access)? as i32 as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         LBU(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u8(access) as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u8(access)? as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         LHU(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u16(access) as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u16(access)? as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SB(SOperands{imm, rs2, rs1}) => {
-            access = r.get(rs1) + imm; m.store_u8(access, r.get(rs2) as u8);
-            *pc += 4;
+            access = r.get(rs1).wrapping_add(imm); m.store_u8(access, r.get(rs2) as u8)?;
+            *pc += len;
             exe_cycles = 1;
             access_op = CacheOp::Write;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         SH(SOperands{imm, rs2, rs1}) => {
-            access = r.get(rs1) + imm; m.store_u16(access, r.get(rs2) as u16);
-            *pc += 4;
+            access = r.get(rs1).wrapping_add(imm); m.store_u16(access, r.get(rs2) as u16)?;
+            *pc += len;
             exe_cycles = 1;
             access_op = CacheOp::Write;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         SW(SOperands{imm, rs2, rs1}) => {
-            access = r.get(rs1) + imm; m.store_u32(access, r.get(rs2) as u32);
-            *pc += 4;
+            access = r.get(rs1).wrapping_add(imm); m.store_u32(access, r.get(rs2) as u32)?;
+            *pc += len;
             exe_cycles = 1;
             access_op = CacheOp::Write;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         ADDI(IOperands{imm, rs1, rd}) => {
-            r.set(rd, r.get(rs1) + imm);
-            *pc += 4;
+            r.set(rd, r.get(rs1).wrapping_add(imm));
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SLTI(IOperands{imm, rs1, rd}) => {
             r.set(rd, if (r.get(rs1) as i64) < (imm as i64) {1} else {0});
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SLTIU(IOperands{imm, rs1, rd}) => {
             r.set(rd, if r.get(rs1) < imm {1} else {0});
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         XORI(IOperands{imm, rs1, rd}) => {
             r.set(rd, r.get(rs1) ^ imm);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         ORI(IOperands{imm, rs1, rd}) => {
             r.set(rd, r.get(rs1) | imm);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         ANDI(IOperands{imm, rs1, rd}) => {
             r.set(rd, r.get(rs1) & imm);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SLLI(IOperands{imm, rs1, rd}) => {
             r.set(rd, r.get(rs1) << (imm & 0b111111));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SRLI(IOperands{imm, rs1, rd}) => {
             r.set(rd, r.get(rs1) >> (imm & 0b111111));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SRAI(IOperands{imm, rs1, rd}) => {
             r.set(rd, ((r.get(rs1) as i64) >> (imm as i64 & 0b111111)) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         ADD(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, r.get(rs1) + r.get(rs2));
-            *pc += 4;
+            r.set(rd, r.get(rs1).wrapping_add(r.get(rs2)));
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SUB(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, r.get(rs1) - r.get(rs2));
-            *pc += 4;
+            r.set(rd, r.get(rs1).wrapping_sub(r.get(rs2)));
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SLL(ROperands{rs2, rs1, rd}) => {
             r.set(rd, r.get(rs1) << (r.get(rs2) & 0b111111));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SLT(ROperands{rs2, rs1, rd}) => {
             r.set(rd, if (r.get(rs1) as i64) < (r.get(rs2) as i64) {1} else {0});
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SLTU(ROperands{rs2, rs1, rd}) => {
             r.set(rd, if r.get(rs1) < r.get(rs2) {1} else {0});
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         XOR(ROperands{rs2, rs1, rd}) => {
             r.set(rd, r.get(rs1) ^ r.get(rs2));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SRL(ROperands{rs2, rs1, rd}) => {
             r.set(rd, r.get(rs1) >> (r.get(rs2) & 0b111111));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SRA(ROperands{rs2, rs1, rd}) => {
             r.set(rd, ((r.get(rs1) as i64) >> (r.get(rs2) as i64 & 0b111111)) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         OR(ROperands{rs2, rs1, rd}) => {
             r.set(rd, r.get(rs1) | r.get(rs2));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         AND(ROperands{rs2, rs1, rd}) => {
             r.set(rd, r.get(rs1) & r.get(rs2));
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         LWU(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u32(access) as i32 as u64);
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u32(access)? as u64);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         LD(IOperands{imm, rs1, rd}) => {
-            access = r.get(rs1) + imm; r.set(rd, m.load_u64(access));
-            *pc += 4; load_reg = rd;
+            access = r.get(rs1).wrapping_add(imm); r.set(rd, m.load_u64(access)?);
+            *pc += len; load_reg = rd;
             exe_cycles = 1;
             access_op = CacheOp::Read;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SD(SOperands{imm, rs2, rs1}) => {
-            access = r.get(rs1) + imm; m.store_u64(access, r.get(rs2));
-            *pc += 4;
+            access = r.get(rs1).wrapping_add(imm); m.store_u64(access, r.get(rs2))?;
+            *pc += len;
             exe_cycles = 1;
             access_op = CacheOp::Write;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
         },
         ADDIW(IOperands{imm, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 + imm as i32) as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as i32).wrapping_add(imm as i32) as u64);
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SLLIW(IOperands{imm, rs1, rd}) => {
             r.set(rd, ((r.get(rs1) as i32) << (imm as i32 & 0b011111)) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SRLIW(IOperands{imm, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 >> (imm as i32 & 0b011111)) as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as u32 >> (imm & 0b11111)) as i32 as u64);
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         SRAIW(IOperands{imm, rs1, rd}) => {
-            r.set(rd, ((r.get(rs1) as i64) >> (imm as i64 & 0b111111)) as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as i32 >> (imm & 0b11111)) as u64);
+            *pc += len;
             exe_cycles = 1;
             reg_read[0] = rs1;
+            reg_write = rd;
         },
         ADDW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 + r.get(rs2) as i32) as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as i32).wrapping_add(r.get(rs2) as i32) as u64);
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SUBW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 - r.get(rs2) as i32) as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as i32).wrapping_sub(r.get(rs2) as i32) as u64);
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SLLW(ROperands{rs2, rs1, rd}) => {
             r.set(rd, ((r.get(rs1) as i32) << (r.get(rs2) as i32 & 0b11111)) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SRLW(ROperands{rs2, rs1, rd}) => {
             r.set(rd, (r.get(rs1) as u32 >> (r.get(rs2) as u32 & 0b11111)) as i32 as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         SRAW(ROperands{rs2, rs1, rd}) => {
             r.set(rd, (r.get(rs1) as i32 >> (r.get(rs2) as i32 & 0b11111)) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 1;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         MUL(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, r.get(rs1) * r.get(rs2));
-            *pc += 4;
+            r.set(rd, r.get(rs1).wrapping_mul(r.get(rs2)));
+            *pc += len;
             exe_cycles = 5;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         MULH(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, ((r.get(rs1) as i128 * r.get(rs2) as i128) >> 64) as u64);
-            *pc += 4;
+            r.set(rd, ((r.get(rs1) as i64 as i128 * r.get(rs2) as i64 as i128) >> 64) as u64);
+            *pc += len;
             exe_cycles = 5;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         MULHSU(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, ((r.get(rs1) as i128 as u128 * r.get(rs2) as u128) >> 64) as u64);
-            *pc += 4;
+            r.set(rd, ((r.get(rs1) as i64 as i128 * r.get(rs2) as i128) >> 64) as u64);
+            *pc += len;
             exe_cycles = 5;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         MULHU(ROperands{rs2, rs1, rd}) => {
             r.set(rd, ((r.get(rs1) as u128 * r.get(rs2) as u128) >> 64) as u64);
-            *pc += 4;
+            *pc += len;
             exe_cycles = 5;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         DIV(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i64 / r.get(rs2) as i64) as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as i64, r.get(rs2) as i64); r.set(rd, if b == 0 { u64::MAX } else { a.wrapping_div(b) as u64 });
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         DIVU(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, r.get(rs1) / r.get(rs2));
-            *pc += 4;
+            r.set(rd, r.get(rs1).checked_div(r.get(rs2)).unwrap_or(u64::MAX));
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         REM(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i64 % r.get(rs2) as i64) as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as i64, r.get(rs2) as i64); r.set(rd, if b == 0 { a as u64 } else { a.wrapping_rem(b) as u64 });
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         REMU(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, r.get(rs1) % r.get(rs2));
-            *pc += 4;
+            let a = r.get(rs1); r.set(rd, a.checked_rem(r.get(rs2)).unwrap_or(a));
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         MULW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as u32 * r.get(rs2) as u32) as i32 as u64);
-            *pc += 4;
+            r.set(rd, (r.get(rs1) as u32).wrapping_mul(r.get(rs2) as u32) as i32 as u64);
+            *pc += len;
             exe_cycles = 5;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         DIVW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 / r.get(rs2) as i32) as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as i32, r.get(rs2) as i32); r.set(rd, if b == 0 { u64::MAX } else { a.wrapping_div(b) as u64 });
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         DIVUW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as u32 / r.get(rs2) as u32) as i32 as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as u32, r.get(rs2) as u32); r.set(rd, a.checked_div(b).map_or(u64::MAX, |q| q as i32 as u64));
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         REMW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as i32 % r.get(rs2) as i32) as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as i32, r.get(rs2) as i32); r.set(rd, if b == 0 { a as u64 } else { a.wrapping_rem(b) as u64 });
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
         },
         REMUW(ROperands{rs2, rs1, rd}) => {
-            r.set(rd, (r.get(rs1) as u32 % r.get(rs2) as u32) as i32 as u64);
-            *pc += 4;
+            let (a, b) = (r.get(rs1) as u32, r.get(rs2) as u32); r.set(rd, a.checked_rem(b).unwrap_or(a) as i32 as u64);
+            *pc += len;
             exe_cycles = 20;
-            reg_read[0] = rs2;
-            reg_read[1] = rs1;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        FENCE(IOperands{imm, rd, ..}) => {
+            let _ = (imm, rd);
+            *pc += len;
+            exe_cycles = 1;
+        },
+        FENCEI(IOperands{imm, rd, ..}) => {
+            let _ = (imm, rd);
+            *pc += len;
+            exe_cycles = 1;
+        },
+        ECALL(EOperands{}) => {
+            raise(c.environment_call())?;
+            exe_cycles = 1;
+        },
+        EBREAK(EOperands{}) => {
+            raise(Exception::Breakpoint(*pc))?;
+            exe_cycles = 1;
+        },
+        CSRRW(IOperands{imm, rs1, rd}) => {
+            let t = if rd.not_zero() { c.read(imm, s)? } else { 0 }; c.write(imm, r.get(rs1))?; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1;
+            reg_write = rd;
+        },
+        CSRRS(IOperands{imm, rs1, rd}) => {
+            let t = c.read(imm, s)?; if rs1.not_zero() { c.write(imm, t | r.get(rs1))? }; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1;
+            reg_write = rd;
+        },
+        CSRRC(IOperands{imm, rs1, rd}) => {
+            let t = c.read(imm, s)?; if rs1.not_zero() { c.write(imm, t & !r.get(rs1))? }; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1;
+            reg_write = rd;
+        },
+        CSRRWI(IOperands{imm, rs1, rd}) => {
+            let t = if rd.not_zero() { c.read(imm, s)? } else { 0 }; c.write(imm, rs1.index() as u64)?; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_write = rd;
+        },
+        CSRRSI(IOperands{imm, rs1, rd}) => {
+            let t = c.read(imm, s)?; if rs1.not_zero() { c.write(imm, t | rs1.index() as u64)? }; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_write = rd;
+        },
+        CSRRCI(IOperands{imm, rs1, rd}) => {
+            let t = c.read(imm, s)?; if rs1.not_zero() { c.write(imm, t & !(rs1.index() as u64))? }; r.set(rd, t);
+            *pc += len;
+            exe_cycles = 1;
+            reg_write = rd;
+        },
+        MRET(EOperands{}) => {
+            *pc = c.trap_return()?;
+            exe_cycles = 1;
+        },
+        SRET(EOperands{}) => {
+            *pc = c.supervisor_return()?;
+            exe_cycles = 1;
+        },
+        WFI(EOperands{}) => {
+            c.wait()?;
+            *pc += len;
+            exe_cycles = 1;
+        },
+        SFENCEVMA(ROperands{rd, ..}) => {
+            if rd.not_zero() { raise(Exception::IllegalInstruction(0))? }; c.sfence_vma()?;
+            *pc += len;
+            exe_cycles = 1;
+        },
+        LRW(ROperands{rs1, rd, ..}) => {
+            access = aligned(r.get(rs1), 4, Exception::LoadAddressMisaligned)?; r.set(rd, m.load_u32(access)? as i32 as u64); *lr = Some(access);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            access_op = CacheOp::Read;
+            reg_read[0] = rs1;
+            reg_write = rd;
+        },
+        SCW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let ok = lr.take() == Some(access); if ok { m.store_u32(access, r.get(rs2) as u32)? }; r.set(rd, !ok as u64);
+            *pc += len;
+            exe_cycles = 1;
+            access_op = CacheOp::Write;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOSWAPW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |_| b)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOADDW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x.wrapping_add(b))?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOXORW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x ^ b)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOANDW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x & b)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOORW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x | b)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMINW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| (x as i32).min(b as i32) as u32)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMAXW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| (x as i32).max(b as i32) as u32)?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMINUW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x.min(b))?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMAXUW(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 4, Exception::StoreAddressMisaligned)?; let b = r.get(rs2) as u32; let t = amo_u32(m, access, |x| x.max(b))?; r.set(rd, t as i32 as u64);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        LRD(ROperands{rs1, rd, ..}) => {
+            access = aligned(r.get(rs1), 8, Exception::LoadAddressMisaligned)?; r.set(rd, m.load_u64(access)?); *lr = Some(access);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            access_op = CacheOp::Read;
+            reg_read[0] = rs1;
+            reg_write = rd;
+        },
+        SCD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let ok = lr.take() == Some(access); if ok { m.store_u64(access, r.get(rs2))? }; r.set(rd, !ok as u64);
+            *pc += len;
+            exe_cycles = 1;
+            access_op = CacheOp::Write;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOSWAPD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |_| b)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOADDD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x.wrapping_add(b))?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOXORD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x ^ b)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOANDD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x & b)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOORD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x | b)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMIND(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| (x as i64).min(b as i64) as u64)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMAXD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| (x as i64).max(b as i64) as u64)?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMINUD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x.min(b))?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        AMOMAXUD(ROperands{rs2, rs1, rd}) => {
+            access = aligned(r.get(rs1), 8, Exception::StoreAddressMisaligned)?; let b = r.get(rs2); let t = amo_u64(m, access, |x| x.max(b))?; r.set(rd, t);
+            *pc += len; load_reg = rd;
+            exe_cycles = 1;
+            rmw = true;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2;
+            reg_write = rd;
+        },
+        FLW(IOperands{imm, rs1, rd}) => {
+            access = r.get(rs1).wrapping_add(imm); f.set_s(rd, f32::from_bits(m.load_u32(access)?));
+            *pc += len; load_reg = rd.fp();
+            exe_cycles = 1;
+            access_op = CacheOp::Read;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FSW(SOperands{imm, rs2, rs1}) => {
+            access = r.get(rs1).wrapping_add(imm); m.store_u32(access, f.get(rs2) as u32)?;
+            *pc += len;
+            exe_cycles = 1;
+            access_op = CacheOp::Write;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2.fp();
+        },
+        FMADDS(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(f.get_s(rs1), f.get_s(rs2), f.get_s(rs3), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FMSUBS(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(f.get_s(rs1), f.get_s(rs2), -f.get_s(rs3), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FNMSUBS(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(-f.get_s(rs1), f.get_s(rs2), f.get_s(rs3), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FNMADDS(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(-f.get_s(rs1), f.get_s(rs2), -f.get_s(rs3), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FADDS(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::add(f.get_s(rs1), f.get_s(rs2), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 4;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSUBS(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::sub(f.get_s(rs1), f.get_s(rs2), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 4;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMULS(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::mul(f.get_s(rs1), f.get_s(rs2), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 4;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FDIVS(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::div(f.get_s(rs1), f.get_s(rs2), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 15;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSQRTS(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::sqrt(f.get_s(rs1), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 20;
+            reg_read[0] = rs1.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJS(ROperands{rs2, rs1, rd}) => {
+            f.set_s(rd, fpu::sgnj(f.get_s(rs1), f.get_s(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJNS(ROperands{rs2, rs1, rd}) => {
+            f.set_s(rd, fpu::sgnjn(f.get_s(rs1), f.get_s(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJXS(ROperands{rs2, rs1, rd}) => {
+            f.set_s(rd, fpu::sgnjx(f.get_s(rs1), f.get_s(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMINS(ROperands{rs2, rs1, rd}) => {
+            f.set_s(rd, fpu::min(f.get_s(rs1), f.get_s(rs2), &mut c.fflags));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMAXS(ROperands{rs2, rs1, rd}) => {
+            f.set_s(rd, fpu::max(f.get_s(rs1), f.get_s(rs2), &mut c.fflags));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FCVTSD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::narrow(f.get_d(rs1), c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd.fp();
+        },
+        FEQS(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::eq(f.get_s(rs1), f.get_s(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FLTS(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::lt(f.get_s(rs1), f.get_s(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FLES(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::le(f.get_s(rs1), f.get_s(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FCLASSS(ROperands{rs1, rd, ..}) => {
+            r.set(rd, fpu::classify(f.get_s(rs1)));
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTWS(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_s(rs1), true, 32, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTWUS(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_s(rs1), false, 32, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTLS(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_s(rs1), true, 64, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTLUS(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_s(rs1), false, 64, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTSW(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i32 as i128, c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTSWU(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as u32 as i128, c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTSL(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i64 as i128, c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTSLU(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i128, c.rounding(rm)?, &mut c.fflags); f.set_s(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FMVXW(ROperands{rs1, rd, ..}) => {
+            r.set(rd, f.get(rs1) as i32 as u64);
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FMVWX(ROperands{rs1, rd, ..}) => {
+            f.set_s(rd, f32::from_bits(r.get(rs1) as u32));
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FLD(IOperands{imm, rs1, rd}) => {
+            access = r.get(rs1).wrapping_add(imm); f.set(rd, m.load_u64(access)?);
+            *pc += len; load_reg = rd.fp();
+            exe_cycles = 1;
+            access_op = CacheOp::Read;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FSD(SOperands{imm, rs2, rs1}) => {
+            access = r.get(rs1).wrapping_add(imm); m.store_u64(access, f.get(rs2))?;
+            *pc += len;
+            exe_cycles = 1;
+            access_op = CacheOp::Write;
+            reg_read[0] = rs1;
+            reg_read[1] = rs2.fp();
+        },
+        FMADDD(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(f.get_d(rs1), f.get_d(rs2), f.get_d(rs3), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FMSUBD(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(f.get_d(rs1), f.get_d(rs2), -f.get_d(rs3), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FNMSUBD(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(-f.get_d(rs1), f.get_d(rs2), f.get_d(rs3), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FNMADDD(R4Operands{rm, rs3, rs2, rs1, rd}) => {
+            let v = fpu::fma(-f.get_d(rs1), f.get_d(rs2), -f.get_d(rs3), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_read[2] = rs3.fp();
+            reg_write = rd.fp();
+        },
+        FADDD(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::add(f.get_d(rs1), f.get_d(rs2), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 4;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSUBD(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::sub(f.get_d(rs1), f.get_d(rs2), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 4;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMULD(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::mul(f.get_d(rs1), f.get_d(rs2), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 5;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FDIVD(FOperands{rm, rs2, rs1, rd}) => {
+            let v = fpu::div(f.get_d(rs1), f.get_d(rs2), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 25;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSQRTD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::sqrt(f.get_d(rs1), c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 30;
+            reg_read[0] = rs1.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJD(ROperands{rs2, rs1, rd}) => {
+            f.set_d(rd, fpu::sgnj(f.get_d(rs1), f.get_d(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJND(ROperands{rs2, rs1, rd}) => {
+            f.set_d(rd, fpu::sgnjn(f.get_d(rs1), f.get_d(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FSGNJXD(ROperands{rs2, rs1, rd}) => {
+            f.set_d(rd, fpu::sgnjx(f.get_d(rs1), f.get_d(rs2)));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMIND(ROperands{rs2, rs1, rd}) => {
+            f.set_d(rd, fpu::min(f.get_d(rs1), f.get_d(rs2), &mut c.fflags));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FMAXD(ROperands{rs2, rs1, rd}) => {
+            f.set_d(rd, fpu::max(f.get_d(rs1), f.get_d(rs2), &mut c.fflags));
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd.fp();
+        },
+        FCVTDS(FOperands{rm, rs1, rd, ..}) => {
+            c.rounding(rm)?; f.set_d(rd, fpu::widen(f.get_s(rs1), &mut c.fflags));
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd.fp();
+        },
+        FEQD(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::eq(f.get_d(rs1), f.get_d(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FLTD(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::lt(f.get_d(rs1), f.get_d(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FLED(ROperands{rs2, rs1, rd}) => {
+            r.set(rd, fpu::le(f.get_d(rs1), f.get_d(rs2), &mut c.fflags) as u64);
+            *pc += len;
+            exe_cycles = 2;
+            reg_read[0] = rs1.fp();
+            reg_read[1] = rs2.fp();
+            reg_write = rd;
+        },
+        FCLASSD(ROperands{rs1, rd, ..}) => {
+            r.set(rd, fpu::classify(f.get_d(rs1)));
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTWD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_d(rs1), true, 32, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTWUD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_d(rs1), false, 32, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTLD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_d(rs1), true, 64, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTLUD(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::to_int(f.get_d(rs1), false, 64, c.rounding(rm)?, &mut c.fflags); r.set(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FCVTDW(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i32 as i128, c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTDWU(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as u32 as i128, c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTDL(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i64 as i128, c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FCVTDLU(FOperands{rm, rs1, rd, ..}) => {
+            let v = fpu::from_int(r.get(rs1) as i128, c.rounding(rm)?, &mut c.fflags); f.set_d(rd, v);
+            *pc += len;
+            exe_cycles = 3;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
+        },
+        FMVXD(ROperands{rs1, rd, ..}) => {
+            r.set(rd, f.get(rs1));
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1.fp();
+            reg_write = rd;
+        },
+        FMVDX(ROperands{rs1, rd, ..}) => {
+            f.set(rd, r.get(rs1));
+            *pc += len;
+            exe_cycles = 1;
+            reg_read[0] = rs1;
+            reg_write = rd.fp();
         },
 
     };
-    let mem_access = if access == 0 { 0 } else { sim.cache.access(access, access_op) };
-    ExecuteInfo {
+    // a DTLB miss walks the page table through the caches
+    let mut walk = 0;
+    for x in &m.walks {
+        walk += sim.dtlb.access(x, &mut sim.cache);
+    }
+    let access = m.paddr.unwrap_or(access);
+    let mem_access = walk + match access {
+        0 => 0,
+        _ if sim.memory.is_device(access) => device::LATENCY,
+        // an AMO reads the line and then writes it back
+        _ if rmw => sim.cache.access(access, CacheOp::Read)
+            + sim.cache.access(access, CacheOp::Write),
+        _ => sim.cache.access(access, access_op),
+    };
+    Ok(ExecuteInfo {
         exe_cycles,
         mem_access,
         load_reg,
+        reg_write,
         reg_read,
         is_branch,
         taken_branch,
-    }
+        is_jump,
+        target,
+    })
 }
 
-pub(crate) fn matching<T>(code: T) -> Instruction
+pub(crate) fn matching<T>(code: T) -> Option<Instruction>
     where T: InstrMatch {
 
-    if code.is_match(UFormat(0b_0110111)) { return LUI(code.decode_U()) }
-    if code.is_match(UFormat(0b_0010111)) { return AUIPC(code.decode_U()) }
-    if code.is_match(JFormat(0b_1101111)) { return JAL(code.decode_J()) }
-    if code.is_match(IFormat(0b_000,0b_1100111)) { return JALR(code.decode_I()) }
-    if code.is_match(BFormat(0b_000,0b_1100011)) { return BEQ(code.decode_B()) }
-    if code.is_match(BFormat(0b_001,0b_1100011)) { return BNE(code.decode_B()) }
-    if code.is_match(BFormat(0b_100,0b_1100011)) { return BLT(code.decode_B()) }
-    if code.is_match(BFormat(0b_101,0b_1100011)) { return BGE(code.decode_B()) }
-    if code.is_match(BFormat(0b_110,0b_1100011)) { return BLTU(code.decode_B()) }
-    if code.is_match(BFormat(0b_111,0b_1100011)) { return BGEU(code.decode_B()) }
-    if code.is_match(IFormat(0b_000,0b_0000011)) { return LB(code.decode_I()) }
-    if code.is_match(IFormat(0b_001,0b_0000011)) { return LH(code.decode_I()) }
-    if code.is_match(IFormat(0b_010,0b_0000011)) { return LW(code.decode_I()) }
-    if code.is_match(IFormat(0b_100,0b_0000011)) { return LBU(code.decode_I()) }
-    if code.is_match(IFormat(0b_101,0b_0000011)) { return LHU(code.decode_I()) }
-    if code.is_match(SFormat(0b_000,0b_0100011)) { return SB(code.decode_S()) }
-    if code.is_match(SFormat(0b_001,0b_0100011)) { return SH(code.decode_S()) }
-    if code.is_match(SFormat(0b_010,0b_0100011)) { return SW(code.decode_S()) }
-    if code.is_match(IFormat(0b_000,0b_0010011)) { return ADDI(code.decode_I()) }
-    if code.is_match(IFormat(0b_010,0b_0010011)) { return SLTI(code.decode_I()) }
-    if code.is_match(IFormat(0b_011,0b_0010011)) { return SLTIU(code.decode_I()) }
-    if code.is_match(IFormat(0b_100,0b_0010011)) { return XORI(code.decode_I()) }
-    if code.is_match(IFormat(0b_110,0b_0010011)) { return ORI(code.decode_I()) }
-    if code.is_match(IFormat(0b_111,0b_0010011)) { return ANDI(code.decode_I()) }
-    if code.is_match(IFormat(0b_001,0b_0010011)) { return SLLI(code.decode_I()) }
-    if code.is_match(IFormat(0b_101,0b_0010011)) { return SRLI(code.decode_I()) }
-    if code.is_match(IFormat(0b_101,0b_0010011)) { return SRAI(code.decode_I()) }
-    if code.is_match(RFormat(0b_0000000,0b_000,0b_0110011)) { return ADD(code.decode_R()) }
-    if code.is_match(RFormat(0b_0100000,0b_000,0b_0110011)) { return SUB(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_001,0b_0110011)) { return SLL(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_010,0b_0110011)) { return SLT(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_011,0b_0110011)) { return SLTU(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_100,0b_0110011)) { return XOR(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_101,0b_0110011)) { return SRL(code.decode_R()) }
-    if code.is_match(RFormat(0b_0100000,0b_101,0b_0110011)) { return SRA(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_110,0b_0110011)) { return OR(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_111,0b_0110011)) { return AND(code.decode_R()) }
-    if code.is_match(IFormat(0b_110,0b_0000011)) { return LWU(code.decode_I()) }
-    if code.is_match(IFormat(0b_011,0b_0000011)) { return LD(code.decode_I()) }
-    if code.is_match(SFormat(0b_011,0b_0100011)) { return SD(code.decode_S()) }
-    if code.is_match(IFormat(0b_000,0b_0011011)) { return ADDIW(code.decode_I()) }
-    if code.is_match(IFormat(0b_001,0b_0011011)) { return SLLIW(code.decode_I()) }
-    if code.is_match(IFormat(0b_101,0b_0011011)) { return SRLIW(code.decode_I()) }
-    if code.is_match(IFormat(0b_101,0b_0011011)) { return SRAIW(code.decode_I()) }
-    if code.is_match(RFormat(0b_0000000,0b_000,0b_0111011)) { return ADDW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0100000,0b_000,0b_0111011)) { return SUBW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_001,0b_0111011)) { return SLLW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000000,0b_101,0b_0111011)) { return SRLW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0100000,0b_101,0b_0111011)) { return SRAW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_000,0b_0110011)) { return MUL(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_001,0b_0110011)) { return MULH(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_010,0b_0110011)) { return MULHSU(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_011,0b_0110011)) { return MULHU(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_100,0b_0110011)) { return DIV(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_101,0b_0110011)) { return DIVU(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_110,0b_0110011)) { return REM(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_111,0b_0110011)) { return REMU(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_000,0b_0111011)) { return MULW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_100,0b_0111011)) { return DIVW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_101,0b_0111011)) { return DIVUW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_110,0b_0111011)) { return REMW(code.decode_R()) }
-    if code.is_match(RFormat(0b_0000001,0b_111,0b_0111011)) { return REMUW(code.decode_R()) }
+    if code.is_match(UFormat(0b_0110111)) { return Some(LUI(code.decode_U())) }
+    if code.is_match(UFormat(0b_0010111)) { return Some(AUIPC(code.decode_U())) }
+    if code.is_match(JFormat(0b_1101111)) { return Some(JAL(code.decode_J())) }
+    if code.is_match(IFormat(0b_000,0b_1100111)) { return Some(JALR(code.decode_I())) }
+    if code.is_match(BFormat(0b_000,0b_1100011)) { return Some(BEQ(code.decode_B())) }
+    if code.is_match(BFormat(0b_001,0b_1100011)) { return Some(BNE(code.decode_B())) }
+    if code.is_match(BFormat(0b_100,0b_1100011)) { return Some(BLT(code.decode_B())) }
+    if code.is_match(BFormat(0b_101,0b_1100011)) { return Some(BGE(code.decode_B())) }
+    if code.is_match(BFormat(0b_110,0b_1100011)) { return Some(BLTU(code.decode_B())) }
+    if code.is_match(BFormat(0b_111,0b_1100011)) { return Some(BGEU(code.decode_B())) }
+    if code.is_match(IFormat(0b_000,0b_0000011)) { return Some(LB(code.decode_I())) }
+    if code.is_match(IFormat(0b_001,0b_0000011)) { return Some(LH(code.decode_I())) }
+    if code.is_match(IFormat(0b_010,0b_0000011)) { return Some(LW(code.decode_I())) }
+    if code.is_match(IFormat(0b_100,0b_0000011)) { return Some(LBU(code.decode_I())) }
+    if code.is_match(IFormat(0b_101,0b_0000011)) { return Some(LHU(code.decode_I())) }
+    if code.is_match(SFormat(0b_000,0b_0100011)) { return Some(SB(code.decode_S())) }
+    if code.is_match(SFormat(0b_001,0b_0100011)) { return Some(SH(code.decode_S())) }
+    if code.is_match(SFormat(0b_010,0b_0100011)) { return Some(SW(code.decode_S())) }
+    if code.is_match(IFormat(0b_000,0b_0010011)) { return Some(ADDI(code.decode_I())) }
+    if code.is_match(IFormat(0b_010,0b_0010011)) { return Some(SLTI(code.decode_I())) }
+    if code.is_match(IFormat(0b_011,0b_0010011)) { return Some(SLTIU(code.decode_I())) }
+    if code.is_match(IFormat(0b_100,0b_0010011)) { return Some(XORI(code.decode_I())) }
+    if code.is_match(IFormat(0b_110,0b_0010011)) { return Some(ORI(code.decode_I())) }
+    if code.is_match(IFormat(0b_111,0b_0010011)) { return Some(ANDI(code.decode_I())) }
+    if code.is_match(HFormat(0b_0000000,0b_001,0b_0010011)) { return Some(SLLI(code.decode_I())) }
+    if code.is_match(HFormat(0b_0000000,0b_101,0b_0010011)) { return Some(SRLI(code.decode_I())) }
+    if code.is_match(HFormat(0b_0100000,0b_101,0b_0010011)) { return Some(SRAI(code.decode_I())) }
+    if code.is_match(RFormat(0b_0000000,0b_000,0b_0110011)) { return Some(ADD(code.decode_R())) }
+    if code.is_match(RFormat(0b_0100000,0b_000,0b_0110011)) { return Some(SUB(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_001,0b_0110011)) { return Some(SLL(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_010,0b_0110011)) { return Some(SLT(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_011,0b_0110011)) { return Some(SLTU(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_100,0b_0110011)) { return Some(XOR(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_101,0b_0110011)) { return Some(SRL(code.decode_R())) }
+    if code.is_match(RFormat(0b_0100000,0b_101,0b_0110011)) { return Some(SRA(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_110,0b_0110011)) { return Some(OR(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_111,0b_0110011)) { return Some(AND(code.decode_R())) }
+    if code.is_match(IFormat(0b_110,0b_0000011)) { return Some(LWU(code.decode_I())) }
+    if code.is_match(IFormat(0b_011,0b_0000011)) { return Some(LD(code.decode_I())) }
+    if code.is_match(SFormat(0b_011,0b_0100011)) { return Some(SD(code.decode_S())) }
+    if code.is_match(IFormat(0b_000,0b_0011011)) { return Some(ADDIW(code.decode_I())) }
+    if code.is_match(HWFormat(0b_0000000,0b_001,0b_0011011)) { return Some(SLLIW(code.decode_I())) }
+    if code.is_match(HWFormat(0b_0000000,0b_101,0b_0011011)) { return Some(SRLIW(code.decode_I())) }
+    if code.is_match(HWFormat(0b_0100000,0b_101,0b_0011011)) { return Some(SRAIW(code.decode_I())) }
+    if code.is_match(RFormat(0b_0000000,0b_000,0b_0111011)) { return Some(ADDW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0100000,0b_000,0b_0111011)) { return Some(SUBW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_001,0b_0111011)) { return Some(SLLW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000000,0b_101,0b_0111011)) { return Some(SRLW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0100000,0b_101,0b_0111011)) { return Some(SRAW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_000,0b_0110011)) { return Some(MUL(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_001,0b_0110011)) { return Some(MULH(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_010,0b_0110011)) { return Some(MULHSU(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_011,0b_0110011)) { return Some(MULHU(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_100,0b_0110011)) { return Some(DIV(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_101,0b_0110011)) { return Some(DIVU(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_110,0b_0110011)) { return Some(REM(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_111,0b_0110011)) { return Some(REMU(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_000,0b_0111011)) { return Some(MULW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_100,0b_0111011)) { return Some(DIVW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_101,0b_0111011)) { return Some(DIVUW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_110,0b_0111011)) { return Some(REMW(code.decode_R())) }
+    if code.is_match(RFormat(0b_0000001,0b_111,0b_0111011)) { return Some(REMUW(code.decode_R())) }
+    if code.is_match(IFormat(0b_000,0b_0001111)) { return Some(FENCE(code.decode_I())) }
+    if code.is_match(IFormat(0b_001,0b_0001111)) { return Some(FENCEI(code.decode_I())) }
+    if code.is_match(EFormat(0b_000000000000,0b_000,0b_1110011)) { return Some(ECALL(code.decode_E())) }
+    if code.is_match(EFormat(0b_000000000001,0b_000,0b_1110011)) { return Some(EBREAK(code.decode_E())) }
+    if code.is_match(IFormat(0b_001,0b_1110011)) { return Some(CSRRW(code.decode_I())) }
+    if code.is_match(IFormat(0b_010,0b_1110011)) { return Some(CSRRS(code.decode_I())) }
+    if code.is_match(IFormat(0b_011,0b_1110011)) { return Some(CSRRC(code.decode_I())) }
+    if code.is_match(IFormat(0b_101,0b_1110011)) { return Some(CSRRWI(code.decode_I())) }
+    if code.is_match(IFormat(0b_110,0b_1110011)) { return Some(CSRRSI(code.decode_I())) }
+    if code.is_match(IFormat(0b_111,0b_1110011)) { return Some(CSRRCI(code.decode_I())) }
+    if code.is_match(EFormat(0b_001100000010,0b_000,0b_1110011)) { return Some(MRET(code.decode_E())) }
+    if code.is_match(EFormat(0b_000100000010,0b_000,0b_1110011)) { return Some(SRET(code.decode_E())) }
+    if code.is_match(EFormat(0b_000100000101,0b_000,0b_1110011)) { return Some(WFI(code.decode_E())) }
+    if code.is_match(RFormat(0b_0001001,0b_000,0b_1110011)) { return Some(SFENCEVMA(code.decode_R())) }
+    if code.is_match(AFormat(0b_0001000,0b_010,0b_0101111)) { return Some(LRW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0001100,0b_010,0b_0101111)) { return Some(SCW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0000100,0b_010,0b_0101111)) { return Some(AMOSWAPW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0000000,0b_010,0b_0101111)) { return Some(AMOADDW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0010000,0b_010,0b_0101111)) { return Some(AMOXORW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0110000,0b_010,0b_0101111)) { return Some(AMOANDW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0100000,0b_010,0b_0101111)) { return Some(AMOORW(code.decode_R())) }
+    if code.is_match(AFormat(0b_1000000,0b_010,0b_0101111)) { return Some(AMOMINW(code.decode_R())) }
+    if code.is_match(AFormat(0b_1010000,0b_010,0b_0101111)) { return Some(AMOMAXW(code.decode_R())) }
+    if code.is_match(AFormat(0b_1100000,0b_010,0b_0101111)) { return Some(AMOMINUW(code.decode_R())) }
+    if code.is_match(AFormat(0b_1110000,0b_010,0b_0101111)) { return Some(AMOMAXUW(code.decode_R())) }
+    if code.is_match(AFormat(0b_0001000,0b_011,0b_0101111)) { return Some(LRD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0001100,0b_011,0b_0101111)) { return Some(SCD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0000100,0b_011,0b_0101111)) { return Some(AMOSWAPD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0000000,0b_011,0b_0101111)) { return Some(AMOADDD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0010000,0b_011,0b_0101111)) { return Some(AMOXORD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0110000,0b_011,0b_0101111)) { return Some(AMOANDD(code.decode_R())) }
+    if code.is_match(AFormat(0b_0100000,0b_011,0b_0101111)) { return Some(AMOORD(code.decode_R())) }
+    if code.is_match(AFormat(0b_1000000,0b_011,0b_0101111)) { return Some(AMOMIND(code.decode_R())) }
+    if code.is_match(AFormat(0b_1010000,0b_011,0b_0101111)) { return Some(AMOMAXD(code.decode_R())) }
+    if code.is_match(AFormat(0b_1100000,0b_011,0b_0101111)) { return Some(AMOMINUD(code.decode_R())) }
+    if code.is_match(AFormat(0b_1110000,0b_011,0b_0101111)) { return Some(AMOMAXUD(code.decode_R())) }
+    if code.is_match(IFormat(0b_010,0b_0000111)) { return Some(FLW(code.decode_I())) }
+    if code.is_match(SFormat(0b_010,0b_0100111)) { return Some(FSW(code.decode_S())) }
+    if code.is_match(R4Format(0b_0000000,0b_1000011)) { return Some(FMADDS(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000000,0b_1000111)) { return Some(FMSUBS(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000000,0b_1001011)) { return Some(FNMSUBS(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000000,0b_1001111)) { return Some(FNMADDS(code.decode_R4())) }
+    if code.is_match(FFormat(0b_0000000,0b_1010011)) { return Some(FADDS(code.decode_F())) }
+    if code.is_match(FFormat(0b_0000100,0b_1010011)) { return Some(FSUBS(code.decode_F())) }
+    if code.is_match(FFormat(0b_0001000,0b_1010011)) { return Some(FMULS(code.decode_F())) }
+    if code.is_match(FFormat(0b_0001100,0b_1010011)) { return Some(FDIVS(code.decode_F())) }
+    if code.is_match(FUFormat(0b_0101100,0b_00000,0b_1010011)) { return Some(FSQRTS(code.decode_F())) }
+    if code.is_match(RFormat(0b_0010000,0b_000,0b_1010011)) { return Some(FSGNJS(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010000,0b_001,0b_1010011)) { return Some(FSGNJNS(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010000,0b_010,0b_1010011)) { return Some(FSGNJXS(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010100,0b_000,0b_1010011)) { return Some(FMINS(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010100,0b_001,0b_1010011)) { return Some(FMAXS(code.decode_R())) }
+    if code.is_match(FUFormat(0b_0100000,0b_00001,0b_1010011)) { return Some(FCVTSD(code.decode_F())) }
+    if code.is_match(RFormat(0b_1010000,0b_010,0b_1010011)) { return Some(FEQS(code.decode_R())) }
+    if code.is_match(RFormat(0b_1010000,0b_001,0b_1010011)) { return Some(FLTS(code.decode_R())) }
+    if code.is_match(RFormat(0b_1010000,0b_000,0b_1010011)) { return Some(FLES(code.decode_R())) }
+    if code.is_match(FXFormat(0b_1110000,0b_00000,0b_001,0b_1010011)) { return Some(FCLASSS(code.decode_R())) }
+    if code.is_match(FUFormat(0b_1100000,0b_00000,0b_1010011)) { return Some(FCVTWS(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100000,0b_00001,0b_1010011)) { return Some(FCVTWUS(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100000,0b_00010,0b_1010011)) { return Some(FCVTLS(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100000,0b_00011,0b_1010011)) { return Some(FCVTLUS(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101000,0b_00000,0b_1010011)) { return Some(FCVTSW(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101000,0b_00001,0b_1010011)) { return Some(FCVTSWU(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101000,0b_00010,0b_1010011)) { return Some(FCVTSL(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101000,0b_00011,0b_1010011)) { return Some(FCVTSLU(code.decode_F())) }
+    if code.is_match(FXFormat(0b_1110000,0b_00000,0b_000,0b_1010011)) { return Some(FMVXW(code.decode_R())) }
+    if code.is_match(FXFormat(0b_1111000,0b_00000,0b_000,0b_1010011)) { return Some(FMVWX(code.decode_R())) }
+    if code.is_match(IFormat(0b_011,0b_0000111)) { return Some(FLD(code.decode_I())) }
+    if code.is_match(SFormat(0b_011,0b_0100111)) { return Some(FSD(code.decode_S())) }
+    if code.is_match(R4Format(0b_0000001,0b_1000011)) { return Some(FMADDD(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000001,0b_1000111)) { return Some(FMSUBD(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000001,0b_1001011)) { return Some(FNMSUBD(code.decode_R4())) }
+    if code.is_match(R4Format(0b_0000001,0b_1001111)) { return Some(FNMADDD(code.decode_R4())) }
+    if code.is_match(FFormat(0b_0000001,0b_1010011)) { return Some(FADDD(code.decode_F())) }
+    if code.is_match(FFormat(0b_0000101,0b_1010011)) { return Some(FSUBD(code.decode_F())) }
+    if code.is_match(FFormat(0b_0001001,0b_1010011)) { return Some(FMULD(code.decode_F())) }
+    if code.is_match(FFormat(0b_0001101,0b_1010011)) { return Some(FDIVD(code.decode_F())) }
+    if code.is_match(FUFormat(0b_0101101,0b_00000,0b_1010011)) { return Some(FSQRTD(code.decode_F())) }
+    if code.is_match(RFormat(0b_0010001,0b_000,0b_1010011)) { return Some(FSGNJD(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010001,0b_001,0b_1010011)) { return Some(FSGNJND(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010001,0b_010,0b_1010011)) { return Some(FSGNJXD(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010101,0b_000,0b_1010011)) { return Some(FMIND(code.decode_R())) }
+    if code.is_match(RFormat(0b_0010101,0b_001,0b_1010011)) { return Some(FMAXD(code.decode_R())) }
+    if code.is_match(FUFormat(0b_0100001,0b_00000,0b_1010011)) { return Some(FCVTDS(code.decode_F())) }
+    if code.is_match(RFormat(0b_1010001,0b_010,0b_1010011)) { return Some(FEQD(code.decode_R())) }
+    if code.is_match(RFormat(0b_1010001,0b_001,0b_1010011)) { return Some(FLTD(code.decode_R())) }
+    if code.is_match(RFormat(0b_1010001,0b_000,0b_1010011)) { return Some(FLED(code.decode_R())) }
+    if code.is_match(FXFormat(0b_1110001,0b_00000,0b_001,0b_1010011)) { return Some(FCLASSD(code.decode_R())) }
+    if code.is_match(FUFormat(0b_1100001,0b_00000,0b_1010011)) { return Some(FCVTWD(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100001,0b_00001,0b_1010011)) { return Some(FCVTWUD(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100001,0b_00010,0b_1010011)) { return Some(FCVTLD(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1100001,0b_00011,0b_1010011)) { return Some(FCVTLUD(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101001,0b_00000,0b_1010011)) { return Some(FCVTDW(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101001,0b_00001,0b_1010011)) { return Some(FCVTDWU(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101001,0b_00010,0b_1010011)) { return Some(FCVTDL(code.decode_F())) }
+    if code.is_match(FUFormat(0b_1101001,0b_00011,0b_1010011)) { return Some(FCVTDLU(code.decode_F())) }
+    if code.is_match(FXFormat(0b_1110001,0b_00000,0b_000,0b_1010011)) { return Some(FMVXD(code.decode_R())) }
+    if code.is_match(FXFormat(0b_1111001,0b_00000,0b_000,0b_1010011)) { return Some(FMVDX(code.decode_R())) }
 
-    panic!("unknown instruction");
+    None
 }
 
@@ -0,0 +1,217 @@
+use crate::action::ExecuteInfo;
+use crate::register::Reg;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BtbConfig {
+    pub entries: u64,
+    pub associativity: u64,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct BtbStats {
+    pub num_access: u64,
+    pub num_miss: u64,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct RasStats {
+    pub num_pop: u64,
+    // returns to somewhere else than the address popped, or with nothing to pop
+    pub num_miss: u64,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+struct BtbEntry {
+    is_valid: bool,
+    pc: u64,
+    target: u64,
+    // last use, for LRU
+    stamp: u64,
+}
+
+/// The targets of taken branches and jumps, looked up by their pc.
+pub struct Btb {
+    stats: BtbStats,
+    sets: Vec<Vec<BtbEntry>>,
+    clock: u64,
+}
+
+impl Btb {
+    pub fn new(config: BtbConfig) -> Self {
+        assert!(config.associativity > 0 && config.entries.is_multiple_of(config.associativity));
+        let num_sets = config.entries / config.associativity;
+        assert!(num_sets.is_power_of_two());
+        Self {
+            stats: Default::default(),
+            sets: vec![vec![BtbEntry::default(); config.associativity as usize]; num_sets as usize],
+            clock: 0,
+        }
+    }
+
+    // with compressed instructions, branches can sit at any even address
+    fn set(&self, pc: u64) -> usize {
+        ((pc >> 1) % self.sets.len() as u64) as usize
+    }
+
+    pub fn lookup(&mut self, pc: u64) -> Option<u64> {
+        self.clock += 1;
+        self.stats.num_access += 1;
+        let (index, clock) = (self.set(pc), self.clock);
+        match self.sets[index].iter_mut().find(|x| x.is_valid && x.pc == pc) {
+            Some(entry) => {
+                entry.stamp = clock;
+                Some(entry.target)
+            }
+            None => {
+                self.stats.num_miss += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, pc: u64, target: u64) {
+        let index = self.set(pc);
+        let set = &mut self.sets[index];
+        let victim = match set.iter().position(|x| x.is_valid && x.pc == pc) {
+            Some(i) => i,
+            None => set.iter().enumerate()
+                .min_by_key(|(_, x)| (x.is_valid, x.stamp))
+                .map(|(i, _)| i)
+                .unwrap(),
+        };
+        set[victim] = BtbEntry { is_valid: true, pc, target, stamp: self.clock };
+    }
+}
+
+/// The return addresses of the calls in flight, dropping the oldest when
+/// there are more than it can hold.
+pub struct ReturnStack {
+    depth: usize,
+    addresses: Vec<u64>,
+    stats: RasStats,
+}
+
+impl ReturnStack {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            addresses: Vec::with_capacity(depth),
+            stats: Default::default(),
+        }
+    }
+
+    pub fn push(&mut self, address: u64) {
+        if self.depth == 0 {
+            return;
+        }
+        if self.addresses.len() == self.depth {
+            self.addresses.remove(0);
+        }
+        self.addresses.push(address);
+    }
+
+    pub fn pop(&mut self) -> Option<u64> {
+        self.stats.num_pop += 1;
+        self.addresses.pop()
+    }
+}
+
+// ra and t0, the link registers of the calling convention
+fn is_link(reg: Reg) -> bool {
+    matches!(reg.index(), 1 | 5)
+}
+
+/// Where fetch goes after a taken branch or jump: returns pop the return
+/// stack, everything else asks the BTB.
+pub struct TargetPredictor {
+    btb: Btb,
+    ras: ReturnStack,
+}
+
+impl TargetPredictor {
+    pub fn new(btb: BtbConfig, ras_depth: usize) -> Self {
+        Self {
+            btb: Btb::new(btb),
+            ras: ReturnStack::new(ras_depth),
+        }
+    }
+
+    // whether fetch would have gone on to `target` after the taken branch or
+    // jump at `pc`, `len` bytes long, and learns that it does
+    pub fn predict(&mut self, pc: u64, len: u64, info: &ExecuteInfo, target: u64) -> bool {
+        // the hints in the JALR description of the ISA manual
+        let (rd, rs1) = (info.reg_write, info.reg_read[0]);
+        let pop = info.is_jump && is_link(rs1) && rd != rs1;
+        let push = info.is_jump && is_link(rd);
+        let predicted = match pop {
+            true => self.ras.pop(),
+            false => self.btb.lookup(pc),
+        };
+        if push {
+            self.ras.push(pc + len);
+        }
+        let hit = predicted == Some(target);
+        if pop && !hit {
+            self.ras.stats.num_miss += 1;
+        }
+        if !pop && !hit {
+            self.btb.insert(pc, target);
+        }
+        hit
+    }
+
+    pub fn output_stats(&self) {
+        let stats = &self.btb.stats;
+        println!("BTB:");
+        println!("  {:?}", stats);
+        println!("  miss rate: {}", stats.num_miss as f32 / stats.num_access as f32);
+        println!("RAS:");
+        println!("  {:?}", self.ras.stats);
+    }
+}
+
+pub const DEFAULT_BTB: BtbConfig = BtbConfig { entries: 512, associativity: 4 };
+pub const DEFAULT_RAS_DEPTH: usize = 16;
+
+pub fn default_targets() -> TargetPredictor {
+    TargetPredictor::new(DEFAULT_BTB, DEFAULT_RAS_DEPTH)
+}
+
+#[test]
+fn test_targets() {
+    let (zero, ra, t0, a0) = (Reg::from(0), Reg::from(1), Reg::from(5), Reg::from(10));
+    let jump = |rd, rs1| ExecuteInfo {
+        is_jump: true,
+        reg_write: rd,
+        reg_read: [rs1, zero, zero],
+        ..Default::default()
+    };
+    let mut targets = TargetPredictor::new(BtbConfig { entries: 4, associativity: 2 }, 2);
+
+    // a call is new to the BTB, its return comes off the stack
+    assert!(!targets.predict(0x1000, 4, &jump(ra, zero), 0x2000));
+    assert!(targets.predict(0x2010, 4, &jump(zero, ra), 0x1004));
+    assert!(targets.predict(0x1000, 4, &jump(ra, zero), 0x2000));
+    // t0 links too, and swapping link registers pops and pushes
+    assert!(!targets.predict(0x2000, 2, &jump(t0, ra), 0x3000));
+    assert!(targets.predict(0x3000, 4, &jump(zero, t0), 0x2002));
+    // an indirect jump, its BTB entry is updated when it goes elsewhere
+    assert!(!targets.predict(0x4000, 4, &jump(zero, a0), 0x5000));
+    assert!(targets.predict(0x4000, 4, &jump(zero, a0), 0x5000));
+    assert!(!targets.predict(0x4000, 4, &jump(zero, a0), 0x6000));
+    assert!(targets.predict(0x4000, 4, &jump(zero, a0), 0x6000));
+
+    // deeper than the stack, the oldest return address is lost
+    for i in 0..3 {
+        targets.predict(0x6000 + 4 * i, 4, &jump(ra, zero), 0x7000);
+    }
+    assert!(targets.predict(0x7000, 4, &jump(zero, ra), 0x600c));
+    assert!(targets.predict(0x7000, 4, &jump(zero, ra), 0x6008));
+    assert!(!targets.predict(0x7000, 4, &jump(zero, ra), 0x6004));
+    assert_eq!(targets.ras.stats.num_miss, 2);
+
+    // a conditional branch reading ra is not a return
+    let branch = ExecuteInfo { is_branch: true, reg_read: [ra, a0, zero], ..Default::default() };
+    assert!(!targets.predict(0x8000, 4, &branch, 0x7ff0));
+    assert!(targets.predict(0x8000, 4, &branch, 0x7ff0));
+}
@@ -96,7 +96,7 @@ pub fn new_3_levels() -> Box<dyn Storage> {
         llc
     ));
 
-    let l1 = Box::new(Cache::new(
+    Box::new(Cache::new(
         CacheConfig {
             name: "L1",
             write_through: false,
@@ -107,9 +107,7 @@ pub fn new_3_levels() -> Box<dyn Storage> {
             latency: 1,
         },
         l2
-    ));
-
-    l1
+    ))
 }
 
 impl CacheLines {
@@ -125,7 +123,7 @@ impl CacheLines {
         let mut result = self.lines.iter_mut()
                                    .filter(|x| x.is_valid)
                                    .find(|x| x.tag == tag);
-        if let Some(mut line) = result.as_mut() {
+        if let Some(line) = result.as_mut() {
             line.last_visit = self.last_visit;
         }
         result 
@@ -137,13 +135,13 @@ impl CacheLines {
 
         self.last_visit += 1;
 
-        let mut line = (match self.lines.iter_mut().find(|x| !x.is_valid) {
+        let line = match self.lines.iter_mut().find(|x| !x.is_valid) {
             Some(x) => x,
             None => match self.lines.iter_mut().min_by_key(|x| x.last_visit) {
                 Some(x) => x,
                 None => panic!("eviction failed"),
             },
-        });
+        };
 
         let result = if line.is_dirty { Some(line.address) } else { None };
 
@@ -180,7 +178,7 @@ impl Cache {
         let tag = address & self.tag_mask;
 
         match lines.find(tag) {
-            Some(line) => self.config.latency,
+            Some(_) => self.config.latency,
             None => {
                 self.stats.num_miss += 1;
                 let eviction_time = 
@@ -0,0 +1,84 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::device::{read_part, write_part, Device, Lines};
+
+const MSIP: u64 = 0x0;
+const MTIMECMP: u64 = 0x4000;
+const MTIME: u64 = 0xbff8;
+
+/// The core-local interruptor of a single hart. mtime counts cycles.
+pub struct Clint {
+    msip: bool,
+    mtimecmp: u64,
+    // mtime minus the current cycle, shared with the time CSR
+    offset: Rc<Cell<u64>>,
+    now: u64,
+    irq: Lines,
+    software: u64,
+    timer: u64,
+}
+
+impl Clint {
+    // raises the `software` and `timer` lines of `irq`, and keeps how far
+    // mtime is ahead of the cycle count in `offset`
+    pub fn new(irq: Lines, software: u64, timer: u64, offset: Rc<Cell<u64>>) -> Self {
+        Self {
+            msip: false,
+            mtimecmp: u64::MAX,
+            offset,
+            now: 0,
+            irq,
+            software,
+            timer,
+        }
+    }
+
+    fn mtime(&self) -> u64 {
+        self.now.wrapping_add(self.offset.get())
+    }
+
+    fn update(&self) {
+        self.irq.set(self.software, self.msip);
+        self.irq.set(self.timer, self.mtime() >= self.mtimecmp);
+    }
+}
+
+impl Device for Clint {
+    fn read(&mut self, offset: u64, size: u64) -> Option<u64> {
+        match offset {
+            MSIP if size == 4 => Some(self.msip as u64),
+            MTIMECMP..=0x4007 => Some(read_part(self.mtimecmp, offset - MTIMECMP, size)),
+            MTIME..=0xbfff => Some(read_part(self.mtime(), offset - MTIME, size)),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, offset: u64, size: u64, value: u64) -> bool {
+        match offset {
+            MSIP if size == 4 => self.msip = value & 1 != 0,
+            MTIMECMP..=0x4007 =>
+                self.mtimecmp = write_part(self.mtimecmp, offset - MTIMECMP, size, value),
+            MTIME..=0xbfff => {
+                let mtime = write_part(self.mtime(), offset - MTIME, size, value);
+                self.offset.set(mtime.wrapping_sub(self.now));
+            }
+            _ => return false,
+        }
+        self.update();
+        true
+    }
+
+    fn tick(&mut self, now: u64) {
+        self.now = now;
+        self.update();
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        if self.mtime() < self.mtimecmp && self.mtimecmp != u64::MAX {
+            Some(self.mtimecmp.wrapping_sub(self.offset.get()))
+        } else {
+            None
+        }
+    }
+}
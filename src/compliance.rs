@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::simulator::{LoadConfig, Simulator};
+
+pub struct Options {
+    // a test still running after this many steps fails
+    pub max_steps: u64,
+    // where NAME.reference_output signatures to compare against are,
+    // next to the test itself by default
+    pub references: Option<PathBuf>,
+    // where to dump NAME.signature
+    pub signatures: Option<PathBuf>,
+    // bytes per signature line, 4 or 8
+    pub granularity: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+}
+
+// the memory between begin_signature and end_signature as riscv-arch-test
+// dumps it, one hex word per line
+pub fn signature(sim: &Simulator, granularity: u64) -> Option<String> {
+    let symbol = |name: &str| sim.elf.symbol_entries.iter()
+        .find(|x| x.0 == name)
+        .map(|x| x.1);
+    let (begin, end) = (symbol("begin_signature")?, symbol("end_signature")?);
+    let mut dump = String::new();
+    for address in (begin..end).step_by(granularity as usize) {
+        let word = match granularity {
+            8 => sim.memory.load_u64(address),
+            _ => sim.memory.load_u32(address).map(|x| x as u64),
+        };
+        dump += &format!("{:0width$x}\n", word.unwrap_or(0), width = 2 * granularity as usize);
+    }
+    Some(dump)
+}
+
+// the line the signatures first differ on, ignoring case and blank lines
+fn compare(signature: &str, reference: &str) -> Option<usize> {
+    let lines = |s: &str| s.lines()
+        .map(|x| x.trim().to_lowercase())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    let (signature, reference) = (lines(signature), lines(reference));
+    (0..signature.len().max(reference.len()))
+        .find(|&i| signature.get(i) != reference.get(i))
+        .map(|i| i + 1)
+}
+
+pub fn run_test(path: &Path, options: &Options) -> Outcome {
+    let filename = path.to_string_lossy();
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let mut sim = Simulator::new();
+    sim.trace = false;
+    if let Err(e) = sim.load_from_elf(&filename, &LoadConfig::new(&filename)) {
+        return Outcome::Fail(e);
+    }
+    if sim.htif.is_none() {
+        return Outcome::Fail("no tohost symbol".to_string());
+    }
+    let mut steps = 0;
+    while sim.run() {
+        steps += 1;
+        if steps == options.max_steps {
+            return Outcome::Fail(format!("still running at pc {:x}", sim.pc));
+        }
+    }
+    if let Some(e) = sim.fault {
+        return Outcome::Fail(e.to_string());
+    }
+    match sim.kernel.exit_code {
+        Some(0) => {}
+        // riscv-tests report the number of the failing test case
+        Some(n) => return Outcome::Fail(format!("test {} failed", n)),
+        None => return Outcome::Fail(format!("stopped at pc {:x}", sim.pc)),
+    }
+
+    let signature = match signature(&sim, options.granularity) {
+        Some(x) => x,
+        None => return Outcome::Pass,
+    };
+    if let Some(dir) = &options.signatures {
+        let path = dir.join(format!("{}.signature", name));
+        if let Err(e) = fs::write(&path, &signature) {
+            return Outcome::Fail(format!("can not write {}: {}", path.display(), e));
+        }
+    }
+    let dir = options.references.as_deref().or_else(|| path.parent()).unwrap_or(Path::new("."));
+    let reference = fs::read_to_string(dir.join(format!("{}.reference_output", name))).ok();
+    match reference.and_then(|x| compare(&signature, &x)) {
+        Some(line) => Outcome::Fail(format!("signature differs at line {}", line)),
+        None => Outcome::Pass,
+    }
+}
+
+fn is_elf(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    path.is_file() && File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok()
+        && magic == *b"\x7fELF"
+}
+
+// rv64ui-p-add belongs to rv64ui-p
+fn suite(name: &str) -> &str {
+    name.rfind('-').map_or(name, |i| &name[..i])
+}
+
+// runs every ELF in `dir` and prints a pass/fail matrix, returns whether
+// all of them passed
+pub fn run_suite(dir: &Path, options: &Options) -> bool {
+    let mut tests: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| is_elf(x))
+            .collect(),
+        Err(e) => {
+            eprintln!("can not read {}: {}", dir.display(), e);
+            return false;
+        }
+    };
+    tests.sort();
+
+    // suite -> (passed, failed)
+    let mut matrix: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for test in &tests {
+        let name = test.file_stem().unwrap().to_string_lossy().into_owned();
+        let counts = matrix.entry(suite(&name).to_string()).or_default();
+        match run_test(test, options) {
+            Outcome::Pass => {
+                counts.0 += 1;
+                println!("PASS {}", name);
+            }
+            Outcome::Fail(why) => {
+                counts.1 += 1;
+                println!("FAIL {}: {}", name, why);
+            }
+        }
+    }
+
+    let width = matrix.keys().map(|x| x.len()).max().unwrap_or(0).max(5);
+    println!();
+    println!("{:<width$} {:>6} {:>6}", "suite", "pass", "fail", width = width);
+    for (suite, (pass, fail)) in &matrix {
+        println!("{:<width$} {:>6} {:>6}", suite, pass, fail, width = width);
+    }
+    let (pass, fail) = matrix.values().fold((0, 0), |x, y| (x.0 + y.0, x.1 + y.1));
+    println!("{:<width$} {:>6} {:>6}", "total", pass, fail, width = width);
+    fail == 0
+}
+
+#[test]
+fn test_signature() {
+    use crate::memory::PERM_RW;
+
+    let mut sim = Simulator::new();
+    sim.memory.alloc(0x2000, 0x100, PERM_RW);
+    sim.memory.store_u64(0x2000, 0xdead_beef_0000_0001).unwrap();
+    sim.memory.store_u64(0x2008, 0xff).unwrap();
+    sim.elf.symbol_entries.push(("begin_signature".to_string(), 0x2000, 0));
+    sim.elf.symbol_entries.push(("end_signature".to_string(), 0x2010, 0));
+
+    let dump = signature(&sim, 4).unwrap();
+    assert_eq!(dump, "00000001\ndeadbeef\n000000ff\n00000000\n");
+    assert_eq!(signature(&sim, 8).unwrap(), "deadbeef00000001\n00000000000000ff\n");
+    assert_eq!(compare(&dump, "00000001\nDEADBEEF\n000000ff\n00000000\n\n"), None);
+    assert_eq!(compare(&dump, "00000001\ndeadbeef\n000000fe\n00000000\n"), Some(3));
+    assert_eq!(compare(&dump, "00000001\n"), Some(2));
+    assert_eq!(suite("rv64ui-p-add"), "rv64ui-p");
+}
+
+#[test]
+fn test_references() {
+    let test = Path::new("tests/compliance/rv64i/add-01.elf");
+    let mut options = Options { max_steps: 10000, references: None, signatures: None, granularity: 4 };
+    assert_eq!(run_test(test, &options), Outcome::Pass);
+
+    // the same test against a reference with its third result changed
+    let dir = std::env::temp_dir().join(format!("riscv-sim-refs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let reference = fs::read_to_string("tests/compliance/rv64i/add-01.reference_output").unwrap();
+    fs::write(dir.join("add-01.reference_output"), reference.replacen("80000000", "7fffffff", 1))
+        .unwrap();
+    options.references = Some(dir.clone());
+    assert_eq!(run_test(test, &options), Outcome::Fail("signature differs at line 5".to_string()));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,219 @@
+// RVC: every 16-bit instruction is expanded to the 32-bit instruction it
+// stands for, so that it goes through the same decoder as the rest.
+
+const OP_LOAD: u32 = 0b0000011;
+const OP_LOAD_FP: u32 = 0b0000111;
+const OP_IMM: u32 = 0b0010011;
+const OP_IMM_32: u32 = 0b0011011;
+const OP_STORE: u32 = 0b0100011;
+const OP_STORE_FP: u32 = 0b0100111;
+const OP: u32 = 0b0110011;
+const OP_32: u32 = 0b0111011;
+const OP_LUI: u32 = 0b0110111;
+const OP_BRANCH: u32 = 0b1100011;
+const OP_JALR: u32 = 0b1100111;
+const OP_JAL: u32 = 0b1101111;
+const EBREAK: u32 = 0x0010_0073;
+
+const SP: u32 = 2;
+const RA: u32 = 1;
+
+fn bits(value: u16, from: u32, to: u32) -> u32 {
+    (value as u32 >> from) & ((1 << (to - from)) - 1)
+}
+
+// scatter the instruction bits [from, to) to bit `start` of an immediate
+fn imm(value: u16, from: u32, to: u32, start: u32) -> u32 {
+    bits(value, from, to) << start
+}
+
+fn sign_extend(value: u32, width: u32) -> u32 {
+    (((value << (32 - width)) as i32) >> (32 - width)) as u32
+}
+
+// the 3-bit register fields address x8-x15
+fn rd_prime(value: u16) -> u32 { bits(value, 2, 5) + 8 }
+
+fn rs1_prime(value: u16) -> u32 { bits(value, 7, 10) + 8 }
+
+fn rd(value: u16) -> u32 { bits(value, 7, 12) }
+
+fn rs2(value: u16) -> u32 { bits(value, 2, 7) }
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, op: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | op
+}
+
+fn i_type(imm: u32, rs1: u32, funct3: u32, rd: u32, op: u32) -> u32 {
+    ((imm & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | op
+}
+
+fn s_type(imm: u32, rs2: u32, rs1: u32, funct3: u32, op: u32) -> u32 {
+    ((imm >> 5 & 0x7f) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12)
+        | ((imm & 0x1f) << 7) | op
+}
+
+fn b_type(imm: u32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    ((imm >> 12 & 1) << 31) | ((imm >> 5 & 0x3f) << 25) | (rs2 << 20) | (rs1 << 15)
+        | (funct3 << 12) | ((imm >> 1 & 0xf) << 8) | ((imm >> 11 & 1) << 7) | OP_BRANCH
+}
+
+fn j_type(imm: u32, rd: u32) -> u32 {
+    ((imm >> 20 & 1) << 31) | ((imm >> 1 & 0x3ff) << 21) | ((imm >> 11 & 1) << 20)
+        | ((imm >> 12 & 0xff) << 12) | (rd << 7) | OP_JAL
+}
+
+// CI-format immediate: imm[5] at bit 12, imm[4:0] at bits 6:2
+fn ci_imm(c: u16) -> u32 {
+    sign_extend(imm(c, 12, 13, 5) | imm(c, 2, 7, 0), 6)
+}
+
+fn cj_offset(c: u16) -> u32 {
+    sign_extend(imm(c, 12, 13, 11) | imm(c, 11, 12, 4) | imm(c, 9, 11, 8)
+        | imm(c, 8, 9, 10) | imm(c, 7, 8, 6) | imm(c, 6, 7, 7)
+        | imm(c, 3, 6, 1) | imm(c, 2, 3, 5), 12)
+}
+
+fn cb_offset(c: u16) -> u32 {
+    sign_extend(imm(c, 12, 13, 8) | imm(c, 10, 12, 3) | imm(c, 5, 7, 6)
+        | imm(c, 3, 5, 1) | imm(c, 2, 3, 5), 9)
+}
+
+// offsets of the word and double-word loads/stores
+fn cl_w(c: u16) -> u32 { imm(c, 10, 13, 3) | imm(c, 6, 7, 2) | imm(c, 5, 6, 6) }
+
+fn cl_d(c: u16) -> u32 { imm(c, 10, 13, 3) | imm(c, 5, 7, 6) }
+
+fn lwsp(c: u16) -> u32 { imm(c, 12, 13, 5) | imm(c, 4, 7, 2) | imm(c, 2, 4, 6) }
+
+fn ldsp(c: u16) -> u32 { imm(c, 12, 13, 5) | imm(c, 5, 7, 3) | imm(c, 2, 5, 6) }
+
+fn swsp(c: u16) -> u32 { imm(c, 9, 13, 2) | imm(c, 7, 9, 6) }
+
+fn sdsp(c: u16) -> u32 { imm(c, 10, 13, 3) | imm(c, 7, 10, 6) }
+
+/// The 32-bit equivalent of an RV64C instruction, `None` if it is reserved.
+pub fn expand(c: u16) -> Option<u32> {
+    let funct3 = bits(c, 13, 16);
+    Some(match (bits(c, 0, 2), funct3) {
+        // C.ADDI4SPN
+        (0b00, 0b000) => {
+            let nzuimm = imm(c, 11, 13, 4) | imm(c, 7, 11, 6) | imm(c, 6, 7, 2) | imm(c, 5, 6, 3);
+            if nzuimm == 0 {
+                return None;
+            }
+            i_type(nzuimm, SP, 0b000, rd_prime(c), OP_IMM)
+        }
+        // C.FLD, C.LW, C.LD
+        (0b00, 0b001) => i_type(cl_d(c), rs1_prime(c), 0b011, rd_prime(c), OP_LOAD_FP),
+        (0b00, 0b010) => i_type(cl_w(c), rs1_prime(c), 0b010, rd_prime(c), OP_LOAD),
+        (0b00, 0b011) => i_type(cl_d(c), rs1_prime(c), 0b011, rd_prime(c), OP_LOAD),
+        // C.FSD, C.SW, C.SD
+        (0b00, 0b101) => s_type(cl_d(c), rd_prime(c), rs1_prime(c), 0b011, OP_STORE_FP),
+        (0b00, 0b110) => s_type(cl_w(c), rd_prime(c), rs1_prime(c), 0b010, OP_STORE),
+        (0b00, 0b111) => s_type(cl_d(c), rd_prime(c), rs1_prime(c), 0b011, OP_STORE),
+
+        // C.ADDI (C.NOP)
+        (0b01, 0b000) => i_type(ci_imm(c), rd(c), 0b000, rd(c), OP_IMM),
+        // C.ADDIW
+        (0b01, 0b001) => {
+            if rd(c) == 0 {
+                return None;
+            }
+            i_type(ci_imm(c), rd(c), 0b000, rd(c), OP_IMM_32)
+        }
+        // C.LI
+        (0b01, 0b010) => i_type(ci_imm(c), 0, 0b000, rd(c), OP_IMM),
+        // C.ADDI16SP
+        (0b01, 0b011) if rd(c) == SP => {
+            let nzimm = sign_extend(imm(c, 12, 13, 9) | imm(c, 6, 7, 4) | imm(c, 5, 6, 6)
+                | imm(c, 3, 5, 7) | imm(c, 2, 3, 5), 10);
+            if nzimm == 0 {
+                return None;
+            }
+            i_type(nzimm, SP, 0b000, SP, OP_IMM)
+        }
+        // C.LUI
+        (0b01, 0b011) => {
+            let nzimm = ci_imm(c);
+            if nzimm == 0 {
+                return None;
+            }
+            (nzimm << 12) | (rd(c) << 7) | OP_LUI
+        }
+        (0b01, 0b100) => {
+            let rd = rs1_prime(c);
+            let shamt = imm(c, 12, 13, 5) | imm(c, 2, 7, 0);
+            match (bits(c, 10, 12), bits(c, 12, 13), bits(c, 5, 7)) {
+                // C.SRLI, C.SRAI, C.ANDI
+                (0b00, _, _) => i_type(shamt, rd, 0b101, rd, OP_IMM),
+                (0b01, _, _) => i_type(0x400 | shamt, rd, 0b101, rd, OP_IMM),
+                (0b10, _, _) => i_type(ci_imm(c), rd, 0b111, rd, OP_IMM),
+                // C.SUB, C.XOR, C.OR, C.AND
+                (0b11, 0, 0b00) => r_type(0b0100000, rd_prime(c), rd, 0b000, rd, OP),
+                (0b11, 0, 0b01) => r_type(0, rd_prime(c), rd, 0b100, rd, OP),
+                (0b11, 0, 0b10) => r_type(0, rd_prime(c), rd, 0b110, rd, OP),
+                (0b11, 0, 0b11) => r_type(0, rd_prime(c), rd, 0b111, rd, OP),
+                // C.SUBW, C.ADDW
+                (0b11, 1, 0b00) => r_type(0b0100000, rd_prime(c), rd, 0b000, rd, OP_32),
+                (0b11, 1, 0b01) => r_type(0, rd_prime(c), rd, 0b000, rd, OP_32),
+                _ => return None,
+            }
+        }
+        // C.J
+        (0b01, 0b101) => j_type(cj_offset(c), 0),
+        // C.BEQZ, C.BNEZ
+        (0b01, 0b110) => b_type(cb_offset(c), 0, rs1_prime(c), 0b000),
+        (0b01, 0b111) => b_type(cb_offset(c), 0, rs1_prime(c), 0b001),
+
+        // C.SLLI
+        (0b10, 0b000) => i_type(imm(c, 12, 13, 5) | imm(c, 2, 7, 0), rd(c), 0b001, rd(c), OP_IMM),
+        // C.FLDSP
+        (0b10, 0b001) => i_type(ldsp(c), SP, 0b011, rd(c), OP_LOAD_FP),
+        // C.LWSP, C.LDSP
+        (0b10, 0b010) | (0b10, 0b011) => {
+            if rd(c) == 0 {
+                return None;
+            }
+            if funct3 == 0b010 {
+                i_type(lwsp(c), SP, 0b010, rd(c), OP_LOAD)
+            } else {
+                i_type(ldsp(c), SP, 0b011, rd(c), OP_LOAD)
+            }
+        }
+        (0b10, 0b100) => match (bits(c, 12, 13), rd(c), rs2(c)) {
+            // C.JR
+            (0, 0, 0) => return None,
+            (0, rs1, 0) => i_type(0, rs1, 0b000, 0, OP_JALR),
+            // C.MV
+            (0, rd, rs2) => r_type(0, rs2, 0, 0b000, rd, OP),
+            // C.EBREAK, C.JALR, C.ADD
+            (1, 0, 0) => EBREAK,
+            (1, rs1, 0) => i_type(0, rs1, 0b000, RA, OP_JALR),
+            (_, rd, rs2) => r_type(0, rs2, rd, 0b000, rd, OP),
+        },
+        // C.FSDSP, C.SWSP, C.SDSP
+        (0b10, 0b101) => s_type(sdsp(c), rs2(c), SP, 0b011, OP_STORE_FP),
+        (0b10, 0b110) => s_type(swsp(c), rs2(c), SP, 0b010, OP_STORE),
+        (0b10, 0b111) => s_type(sdsp(c), rs2(c), SP, 0b011, OP_STORE),
+        _ => return None,
+    })
+}
+
+#[test]
+fn test001() {
+    // encodings taken from llvm-objdump
+    assert_eq!(expand(0x0000), None);
+    assert_eq!(expand(0x1141), Some(0xff010113));   // addi sp, sp, -16
+    assert_eq!(expand(0xe406), Some(0x00113423));   // sd ra, 8(sp)
+    assert_eq!(expand(0x60a2), Some(0x00813083));   // ld ra, 8(sp)
+    assert_eq!(expand(0x4501), Some(0x00000513));   // li a0, 0
+    assert_eq!(expand(0x8082), Some(0x00008067));   // ret
+    assert_eq!(expand(0x852e), Some(0x00b00533));   // mv a0, a1
+    assert_eq!(expand(0x9d2d), Some(0x00b5053b));   // addw a0, a0, a1
+    assert_eq!(expand(0x8d89), Some(0x40a585b3));   // sub a1, a1, a0
+    assert_eq!(expand(0x0028), Some(0x00810513));   // addi a0, sp, 8
+    assert_eq!(expand(0xa001), Some(0x0000006f));   // j .
+    assert_eq!(expand(0xdd7d), Some(0xfe050fe3));   // beqz a0, -2
+    assert_eq!(expand(0x9002), Some(0x00100073));   // ebreak
+}
@@ -0,0 +1,562 @@
+use crate::fpu;
+use crate::mmu::Translation;
+use crate::pmp::Pmp;
+use crate::statistic::Statistic;
+use crate::trap::Exception;
+
+pub const FFLAGS: u64 = 0x001;
+pub const FRM: u64 = 0x002;
+pub const FCSR: u64 = 0x003;
+pub const SSTATUS: u64 = 0x100;
+pub const SIE: u64 = 0x104;
+pub const STVEC: u64 = 0x105;
+pub const SCOUNTEREN: u64 = 0x106;
+pub const SSCRATCH: u64 = 0x140;
+pub const SEPC: u64 = 0x141;
+pub const SCAUSE: u64 = 0x142;
+pub const STVAL: u64 = 0x143;
+pub const SIP: u64 = 0x144;
+pub const SATP: u64 = 0x180;
+pub const MSTATUS: u64 = 0x300;
+pub const MISA: u64 = 0x301;
+pub const MEDELEG: u64 = 0x302;
+pub const MIDELEG: u64 = 0x303;
+pub const MIE: u64 = 0x304;
+pub const MTVEC: u64 = 0x305;
+pub const MCOUNTEREN: u64 = 0x306;
+pub const PMPCFG0: u64 = 0x3a0;
+pub const PMPCFG2: u64 = 0x3a2;
+pub const PMPADDR0: u64 = 0x3b0;
+pub const PMPADDR15: u64 = 0x3bf;
+pub const MSCRATCH: u64 = 0x340;
+pub const MEPC: u64 = 0x341;
+pub const MCAUSE: u64 = 0x342;
+pub const MTVAL: u64 = 0x343;
+pub const MIP: u64 = 0x344;
+pub const MCYCLE: u64 = 0xb00;
+pub const MINSTRET: u64 = 0xb02;
+pub const CYCLE: u64 = 0xc00;
+pub const TIME: u64 = 0xc01;
+pub const INSTRET: u64 = 0xc02;
+pub const MVENDORID: u64 = 0xf11;
+pub const MARCHID: u64 = 0xf12;
+pub const MIMPID: u64 = 0xf13;
+pub const MHARTID: u64 = 0xf14;
+
+pub const MSTATUS_SIE: u64 = 1 << 1;
+pub const MSTATUS_MIE: u64 = 1 << 3;
+pub const MSTATUS_SPIE: u64 = 1 << 5;
+pub const MSTATUS_MPIE: u64 = 1 << 7;
+pub const MSTATUS_SPP: u64 = 1 << 8;
+pub const MSTATUS_MPP: u64 = 0b11 << 11;
+pub const MSTATUS_FS: u64 = 0b11 << 13;
+pub const MSTATUS_MPRV: u64 = 1 << 17;
+pub const MSTATUS_SUM: u64 = 1 << 18;
+pub const MSTATUS_MXR: u64 = 1 << 19;
+pub const MSTATUS_TVM: u64 = 1 << 20;
+pub const MSTATUS_TW: u64 = 1 << 21;
+pub const MSTATUS_TSR: u64 = 1 << 22;
+pub const MSTATUS_UXL: u64 = 0b11 << 32;
+pub const MSTATUS_SD: u64 = 1 << 63;
+
+// the part of mstatus visible as sstatus
+const SSTATUS_MASK: u64 = MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_FS
+    | MSTATUS_SUM | MSTATUS_MXR | MSTATUS_UXL | MSTATUS_SD;
+
+pub const MIP_SSIP: u64 = 1 << 1;
+pub const MIP_MSIP: u64 = 1 << 3;
+pub const MIP_STIP: u64 = 1 << 5;
+pub const MIP_MTIP: u64 = 1 << 7;
+pub const MIP_SEIP: u64 = 1 << 9;
+pub const MIP_MEIP: u64 = 1 << 11;
+
+const MIP_S: u64 = MIP_SSIP | MIP_STIP | MIP_SEIP;
+// mcause of an interrupt
+const INTERRUPT: u64 = 1 << 63;
+// the order simultaneous interrupts are taken in
+const INTERRUPT_PRIORITY: [u64; 6] = [11, 3, 7, 9, 1, 5];
+const MIP_M: u64 = MIP_MSIP | MIP_MTIP | MIP_MEIP;
+
+// every exception but ECALL from M-mode can be delegated
+const MEDELEG_MASK: u64 = 0xb3ff;
+
+const MXL_64: u64 = 2 << 62;
+const XL_64: u64 = 2;
+const SATP_MODES: [u64; 3] = [0, 8, 9];
+
+fn extension(c: char) -> u64 {
+    1 << (c as u8 - b'A')
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Privilege {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl Privilege {
+    // the encoding used by mstatus.MPP, 2 is reserved
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        match bits & 0b11 {
+            0 => Some(Privilege::User),
+            1 => Some(Privilege::Supervisor),
+            3 => Some(Privilege::Machine),
+            _ => None,
+        }
+    }
+}
+
+fn illegal() -> Exception {
+    Exception::IllegalInstruction(0)
+}
+
+/// Control and status registers of the single hart, together with its
+/// privilege level. The counters are not stored here but mirror
+/// `Statistic`, so that `rdcycle`/`rdinstret` report the simulator's
+/// own numbers.
+pub struct CsrFile {
+    pub privilege: Privilege,
+    pub mstatus: u64,
+    pub misa: u64,
+    pub mie: u64,
+    pub mip: u64,
+    // mip bits raised by the CLINT and PLIC
+    pub lines: u64,
+    // how far the CLINT's mtime is ahead of the cycle count
+    pub time_offset: u64,
+    pub mtvec: u64,
+    pub mscratch: u64,
+    pub mepc: u64,
+    pub mcause: u64,
+    pub mtval: u64,
+    pub medeleg: u64,
+    pub mideleg: u64,
+    pub mcounteren: u64,
+    pub stvec: u64,
+    pub sscratch: u64,
+    pub sepc: u64,
+    pub scause: u64,
+    pub stval: u64,
+    pub scounteren: u64,
+    pub satp: u64,
+    pub pmp: Pmp,
+    pub fflags: u64,
+    pub frm: u64,
+}
+
+impl CsrFile {
+    pub fn new() -> Self {
+        Self {
+            privilege: Privilege::Machine,
+            // MRET without a trap stays in M-mode, the FP unit is
+            // always on and U/S-mode are 64-bit (UXL and SXL)
+            mstatus: MSTATUS_MPP | MSTATUS_FS | MSTATUS_SD
+                | XL_64 << 32 | XL_64 << 34,
+            misa: MXL_64 | extension('I') | extension('M') | extension('A')
+                | extension('F') | extension('D') | extension('C')
+                | extension('S') | extension('U'),
+            mie: 0,
+            mip: 0,
+            lines: 0,
+            time_offset: 0,
+            mtvec: 0,
+            mscratch: 0,
+            mepc: 0,
+            mcause: 0,
+            mtval: 0,
+            medeleg: 0,
+            mideleg: 0,
+            mcounteren: 0,
+            stvec: 0,
+            sscratch: 0,
+            sepc: 0,
+            scause: 0,
+            stval: 0,
+            scounteren: 0,
+            satp: 0,
+            pmp: Pmp::default(),
+            fflags: 0,
+            frm: 0,
+        }
+    }
+
+    fn check_access(&self, csr: u64) -> Result<(), Exception> {
+        // bits 9:8 hold the lowest privilege that may access it
+        if (csr >> 8) & 0b11 > self.privilege as u64 {
+            return Err(illegal());
+        }
+        if csr == SATP && self.privilege == Privilege::Supervisor
+            && self.mstatus & MSTATUS_TVM != 0 {
+            return Err(illegal());
+        }
+        if (CYCLE..=INSTRET).contains(&csr) {
+            let bit = 1 << (csr - CYCLE);
+            let enabled = match self.privilege {
+                Privilege::Machine => true,
+                Privilege::Supervisor => self.mcounteren & bit != 0,
+                Privilege::User => self.mcounteren & self.scounteren & bit != 0,
+            };
+            if !enabled {
+                return Err(illegal());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, csr: u64, stat: &Statistic) -> Result<u64, Exception> {
+        let csr = csr & 0xfff;
+        self.check_access(csr)?;
+        Ok(match csr {
+            FFLAGS => self.fflags,
+            FRM => self.frm,
+            FCSR => (self.frm << 5) | self.fflags,
+            SSTATUS => self.mstatus & SSTATUS_MASK,
+            SIE => self.mie & self.mideleg,
+            STVEC => self.stvec,
+            SCOUNTEREN => self.scounteren,
+            SSCRATCH => self.sscratch,
+            SEPC => self.sepc,
+            SCAUSE => self.scause,
+            STVAL => self.stval,
+            SIP => (self.mip | self.lines) & self.mideleg,
+            SATP => self.satp,
+            MSTATUS => self.mstatus,
+            MISA => self.misa,
+            MEDELEG => self.medeleg,
+            MIDELEG => self.mideleg,
+            MIE => self.mie,
+            MIP => self.mip | self.lines,
+            MTVEC => self.mtvec,
+            MCOUNTEREN => self.mcounteren,
+            PMPCFG0 => self.pmp.read_cfg(0),
+            PMPCFG2 => self.pmp.read_cfg(1),
+            PMPADDR0..=PMPADDR15 => self.pmp.read_addr((csr - PMPADDR0) as usize),
+            MSCRATCH => self.mscratch,
+            MEPC => self.mepc,
+            MCAUSE => self.mcause,
+            MTVAL => self.mtval,
+            MCYCLE | CYCLE => stat.cycle,
+            TIME => stat.cycle.wrapping_add(self.time_offset),
+            MINSTRET | INSTRET => stat.num_inst,
+            MVENDORID | MARCHID | MIMPID | MHARTID => 0,
+            _ => return Err(illegal()),
+        })
+    }
+
+    pub fn write(&mut self, csr: u64, value: u64) -> Result<(), Exception> {
+        let csr = csr & 0xfff;
+        self.check_access(csr)?;
+        if csr >> 10 == 0b11 {
+            return Err(illegal());
+        }
+        match csr {
+            FFLAGS => self.fflags = value & 0x1f,
+            FRM => self.frm = value & 0b111,
+            FCSR => {
+                self.fflags = value & 0x1f;
+                self.frm = (value >> 5) & 0b111;
+            }
+            SSTATUS => {
+                let mask = MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP
+                    | MSTATUS_SUM | MSTATUS_MXR;
+                self.mstatus = (self.mstatus & !mask) | (value & mask);
+            }
+            SIE => self.mie = (self.mie & !self.mideleg) | (value & self.mideleg),
+            STVEC => self.stvec = value & !0b10,
+            SCOUNTEREN => self.scounteren = value & 0b111,
+            SSCRATCH => self.sscratch = value,
+            SEPC => self.sepc = value & !0b1,
+            SCAUSE => self.scause = value,
+            STVAL => self.stval = value,
+            SIP => {
+                let mask = MIP_SSIP & self.mideleg;
+                self.mip = (self.mip & !mask) | (value & mask);
+            }
+            // unsupported modes leave satp unchanged
+            SATP => if SATP_MODES.contains(&(value >> 60)) {
+                self.satp = value
+            },
+            MSTATUS => {
+                let mut mask = MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_SIE | MSTATUS_SPIE
+                    | MSTATUS_SPP | MSTATUS_MPRV | MSTATUS_SUM | MSTATUS_MXR
+                    | MSTATUS_TVM | MSTATUS_TW | MSTATUS_TSR;
+                if Privilege::from_bits(value >> 11).is_some() {
+                    mask |= MSTATUS_MPP;
+                }
+                self.mstatus = (self.mstatus & !mask) | (value & mask);
+            }
+            MEDELEG => self.medeleg = value & MEDELEG_MASK,
+            MIDELEG => self.mideleg = value & MIP_S,
+            MIE => self.mie = value & (MIP_M | MIP_S),
+            // MSIP/MTIP/MEIP are driven by the platform, not by software
+            MIP => self.mip = (self.mip & !MIP_S) | (value & MIP_S),
+            // direct and vectored are the only valid modes
+            MTVEC => self.mtvec = value & !0b10,
+            MCOUNTEREN => self.mcounteren = value & 0b111,
+            PMPCFG0 => self.pmp.write_cfg(0, value),
+            PMPCFG2 => self.pmp.write_cfg(1, value),
+            PMPADDR0..=PMPADDR15 => self.pmp.write_addr((csr - PMPADDR0) as usize, value),
+            MSCRATCH => self.mscratch = value,
+            MEPC => self.mepc = value & !0b1,
+            MCAUSE => self.mcause = value,
+            MTVAL => self.mtval = value,
+            // misa is not writable and the counters follow Statistic
+            MISA | MCYCLE | MINSTRET => {}
+            _ => return Err(illegal()),
+        }
+        Ok(())
+    }
+
+    // what loads, stores and fetches are translated with
+    pub fn translation(&self) -> Translation {
+        let data = if self.mstatus & MSTATUS_MPRV != 0 {
+            Privilege::from_bits(self.mstatus >> 11).unwrap()
+        } else {
+            self.privilege
+        };
+        Translation {
+            satp: self.satp,
+            data,
+            fetch: self.privilege,
+            sum: self.mstatus & MSTATUS_SUM != 0,
+            mxr: self.mstatus & MSTATUS_MXR != 0,
+            pmp: self.pmp,
+        }
+    }
+
+    pub fn environment_call(&self) -> Exception {
+        match self.privilege {
+            Privilege::User => Exception::EnvironmentCallFromUMode,
+            Privilege::Supervisor => Exception::EnvironmentCallFromSMode,
+            Privilege::Machine => Exception::EnvironmentCallFromMMode,
+        }
+    }
+
+    pub fn sfence_vma(&self) -> Result<(), Exception> {
+        match self.privilege {
+            Privilege::User => Err(illegal()),
+            Privilege::Supervisor if self.mstatus & MSTATUS_TVM != 0 => Err(illegal()),
+            _ => Ok(()),
+        }
+    }
+
+    // the rounding mode of an FP instruction, DYN selects frm
+    pub fn rounding(&self, rm: u64) -> Result<u64, Exception> {
+        let rm = if rm == fpu::DYN { self.frm } else { rm };
+        if rm > fpu::RMM {
+            return Err(illegal());
+        }
+        Ok(rm)
+    }
+
+    // WFI, which may wait forever, so it is not allowed in U-mode
+    pub fn wait(&self) -> Result<(), Exception> {
+        match self.privilege {
+            Privilege::User => Err(illegal()),
+            Privilege::Supervisor if self.mstatus & MSTATUS_TW != 0 => Err(illegal()),
+            _ => Ok(()),
+        }
+    }
+
+    // interrupts pending and enabled in mie, which end a WFI
+    pub fn pending(&self) -> u64 {
+        (self.mip | self.lines) & self.mie
+    }
+
+    // the interrupt to take before the next instruction
+    pub fn interrupt(&self) -> Option<u64> {
+        let pending = self.pending();
+        let m_enabled = self.privilege != Privilege::Machine || self.mstatus & MSTATUS_MIE != 0;
+        // delegated interrupts are never taken in M-mode
+        let s_enabled = self.privilege == Privilege::User
+            || (self.privilege == Privilege::Supervisor && self.mstatus & MSTATUS_SIE != 0);
+        let m = if m_enabled { pending & !self.mideleg } else { 0 };
+        let s = if s_enabled { pending & self.mideleg } else { 0 };
+        let first = |x: u64| INTERRUPT_PRIORITY.iter().copied().find(|i| (x >> i) & 1 != 0);
+        first(m).or_else(|| first(s))
+    }
+
+    // delegated exceptions below M-mode are taken in S-mode
+    fn delegates(&self, e: Exception) -> bool {
+        self.privilege != Privilege::Machine && (self.medeleg >> e.cause()) & 1 != 0
+    }
+
+    // the trap vector `e` would be taken to, 0 when there is no handler
+    pub fn trap_vector(&self, e: Exception) -> u64 {
+        if self.delegates(e) { self.stvec } else { self.mtvec }
+    }
+
+    // returns the address of the trap handler
+    pub fn trap_enter(&mut self, pc: u64, e: Exception) -> u64 {
+        let delegated = self.delegates(e);
+        self.enter(pc, e.cause(), e.tval(), delegated)
+    }
+
+    // returns the address of the interrupt handler, the trap vector may be vectored
+    pub fn interrupt_enter(&mut self, pc: u64, code: u64) -> u64 {
+        let delegated = (self.mideleg >> code) & 1 != 0;
+        let tvec = if delegated { self.stvec } else { self.mtvec };
+        let handler = self.enter(pc, INTERRUPT | code, 0, delegated);
+        if tvec & 1 != 0 { handler + 4 * code } else { handler }
+    }
+
+    fn enter(&mut self, pc: u64, cause: u64, tval: u64, delegated: bool) -> u64 {
+        if delegated {
+            self.sepc = pc;
+            self.scause = cause;
+            self.stval = tval;
+            let sie = self.mstatus & MSTATUS_SIE != 0;
+            self.mstatus &= !(MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP);
+            if sie {
+                self.mstatus |= MSTATUS_SPIE;
+            }
+            if self.privilege == Privilege::Supervisor {
+                self.mstatus |= MSTATUS_SPP;
+            }
+            self.privilege = Privilege::Supervisor;
+            return self.stvec & !0b11;
+        }
+        self.mepc = pc;
+        self.mcause = cause;
+        self.mtval = tval;
+        let mie = self.mstatus & MSTATUS_MIE != 0;
+        self.mstatus &= !(MSTATUS_MIE | MSTATUS_MPIE);
+        if mie {
+            self.mstatus |= MSTATUS_MPIE;
+        }
+        self.mstatus = (self.mstatus & !MSTATUS_MPP) | (self.privilege as u64) << 11;
+        self.privilege = Privilege::Machine;
+        self.mtvec & !0b11
+    }
+
+    // MRET: returns the address to resume at
+    pub fn trap_return(&mut self) -> Result<u64, Exception> {
+        if self.privilege != Privilege::Machine {
+            return Err(illegal());
+        }
+        let mpp = Privilege::from_bits(self.mstatus >> 11).unwrap();
+        let mpie = self.mstatus & MSTATUS_MPIE != 0;
+        self.mstatus = (self.mstatus & !(MSTATUS_MIE | MSTATUS_MPP)) | MSTATUS_MPIE;
+        if mpie {
+            self.mstatus |= MSTATUS_MIE;
+        }
+        if mpp != Privilege::Machine {
+            self.mstatus &= !MSTATUS_MPRV;
+        }
+        self.privilege = mpp;
+        Ok(self.mepc)
+    }
+
+    // SRET: returns the address to resume at
+    pub fn supervisor_return(&mut self) -> Result<u64, Exception> {
+        match self.privilege {
+            Privilege::User => return Err(illegal()),
+            Privilege::Supervisor if self.mstatus & MSTATUS_TSR != 0 => return Err(illegal()),
+            _ => {}
+        }
+        let spp = if self.mstatus & MSTATUS_SPP != 0 {
+            Privilege::Supervisor
+        } else {
+            Privilege::User
+        };
+        let spie = self.mstatus & MSTATUS_SPIE != 0;
+        self.mstatus = (self.mstatus & !(MSTATUS_SIE | MSTATUS_SPP | MSTATUS_MPRV))
+            | MSTATUS_SPIE;
+        if spie {
+            self.mstatus |= MSTATUS_SIE;
+        }
+        self.privilege = spp;
+        Ok(self.sepc)
+    }
+}
+
+#[test]
+fn test_counters() {
+    let mut c = CsrFile::new();
+    let mut stat = Statistic::default();
+    stat.cycle = 100;
+    stat.num_inst = 40;
+    // rdcycle and rdinstret, and the M-mode counters behind them
+    assert_eq!(c.read(CYCLE, &stat), Ok(100));
+    assert_eq!(c.read(INSTRET, &stat), Ok(40));
+    assert_eq!(c.read(MCYCLE, &stat), Ok(100));
+    assert_eq!(c.read(MINSTRET, &stat), Ok(40));
+    assert_eq!(c.write(MCYCLE, 0), Ok(()));
+    assert_eq!(c.read(MCYCLE, &stat), Ok(100));
+
+    // nothing at 0x7c0, and the user counters and IDs are read-only
+    assert_eq!(c.read(0x7c0, &stat), Err(Exception::IllegalInstruction(0)));
+    assert_eq!(c.write(0x7c0, 1), Err(Exception::IllegalInstruction(0)));
+    assert_eq!(c.write(CYCLE, 0), Err(Exception::IllegalInstruction(0)));
+    assert_eq!(c.write(MHARTID, 0), Err(Exception::IllegalInstruction(0)));
+}
+
+#[test]
+fn test_delegation() {
+    let stat = Statistic::default();
+    let mut c = CsrFile::new();
+    c.write(MEDELEG, 1 << 8).unwrap();
+    c.write(STVEC, 0x2000).unwrap();
+    c.write(MTVEC, 0x1000).unwrap();
+    // MRET with MPP = U
+    c.write(MSTATUS, 0).unwrap();
+    c.mepc = 0x400;
+    assert_eq!(c.trap_return(), Ok(0x400));
+    assert_eq!(c.privilege, Privilege::User);
+    assert_eq!(c.read(MSTATUS, &stat), Err(illegal()));
+    assert_eq!(c.read(CYCLE, &stat), Err(illegal()));
+    // ECALL from U-mode goes to S-mode, an illegal instruction to M-mode
+    let e = c.environment_call();
+    assert_eq!(c.trap_enter(0x404, e), 0x2000);
+    assert_eq!((c.privilege, c.scause, c.sepc), (Privilege::Supervisor, 8, 0x404));
+    assert_eq!(c.supervisor_return(), Ok(0x404));
+    assert_eq!(c.privilege, Privilege::User);
+    assert_eq!(c.trap_enter(0x408, illegal()), 0x1000);
+    assert_eq!((c.privilege, c.mstatus & MSTATUS_MPP), (Privilege::Machine, 0));
+    // without mtvec, delegated exceptions still have S-mode's handler
+    c.write(MTVEC, 0).unwrap();
+    c.privilege = Privilege::User;
+    assert_eq!(c.trap_vector(c.environment_call()), 0x2000);
+    assert_eq!(c.trap_vector(illegal()), 0);
+    c.privilege = Privilege::Machine;
+    assert_eq!(c.trap_vector(Exception::EnvironmentCallFromUMode), 0);
+}
+
+#[test]
+fn test_interrupt() {
+    let mut c = CsrFile::new();
+    c.write(MTVEC, 0x1001).unwrap();
+    c.write(STVEC, 0x2000).unwrap();
+    c.write(MIDELEG, MIP_STIP).unwrap();
+    c.write(MIE, MIP_MTIP | MIP_MSIP | MIP_STIP).unwrap();
+    c.lines = MIP_MTIP | MIP_MSIP;
+    // M-mode with mstatus.MIE clear takes nothing
+    assert_eq!(c.interrupt(), None);
+    assert_ne!(c.pending(), 0);
+    c.write(MSTATUS, MSTATUS_MIE).unwrap();
+    // software before timer, vectored
+    assert_eq!(c.interrupt(), Some(3));
+    assert_eq!(c.interrupt_enter(0x400, 3), 0x100c);
+    assert_eq!((c.mcause, c.mepc), (INTERRUPT | 3, 0x400));
+    assert_eq!(c.interrupt(), None);
+
+    // a delegated interrupt waits for M-mode interrupts and sstatus.SIE,
+    // and is not taken in M-mode at all
+    c.lines = MIP_MTIP;
+    c.write(MIP, MIP_STIP).unwrap();
+    c.mstatus &= !MSTATUS_MPP;
+    c.mstatus |= 1 << 11;
+    c.trap_return().unwrap();
+    assert_eq!(c.interrupt(), Some(7));
+    c.lines = 0;
+    assert_eq!(c.interrupt(), None);
+    c.mstatus |= MSTATUS_SIE;
+    assert_eq!(c.interrupt(), Some(5));
+    assert_eq!(c.interrupt_enter(0x800, 5), 0x2000);
+    assert_eq!((c.privilege, c.scause, c.sepc), (Privilege::Supervisor, INTERRUPT | 5, 0x800));
+    assert_eq!(c.interrupt(), None);
+    c.privilege = Privilege::Machine;
+    c.write(MSTATUS, MSTATUS_MIE | MSTATUS_SIE).unwrap();
+    assert_eq!(c.interrupt(), None);
+}
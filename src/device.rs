@@ -0,0 +1,183 @@
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::rc::Rc;
+
+use crate::clint::Clint;
+use crate::csr::{MIP_MEIP, MIP_MSIP, MIP_MTIP, MIP_SEIP};
+use crate::plic::Plic;
+use crate::uart::Uart;
+
+// the QEMU `virt` machine
+pub const CLINT_BASE: u64 = 0x200_0000;
+pub const CLINT_SIZE: u64 = 0x1_0000;
+pub const PLIC_BASE: u64 = 0xc00_0000;
+pub const PLIC_SIZE: u64 = 0x400_0000;
+pub const UART_BASE: u64 = 0x1000_0000;
+pub const UART_SIZE: u64 = 0x100;
+pub const UART_IRQ: u32 = 10;
+
+// device registers are not cached
+pub const LATENCY: u64 = 1;
+
+/// A memory-mapped device. `offset` is relative to the start of the range
+/// it is attached at, and a `None` or `false` raises an access fault.
+pub trait Device {
+    fn read(&mut self, offset: u64, size: u64) -> Option<u64>;
+    fn write(&mut self, offset: u64, size: u64, value: u64) -> bool;
+    // called between instructions with the current cycle
+    fn tick(&mut self, _now: u64) {}
+    // the cycle at which it will raise an interrupt by itself
+    fn next_event(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Interrupt wires, one bit per line, shared by the devices driving them
+/// and the one listening.
+#[derive(Clone, Default)]
+pub struct Lines(Rc<Cell<u64>>);
+
+impl Lines {
+    // drives the lines in `mask`
+    pub fn set(&self, mask: u64, level: bool) {
+        self.0.set(if level { self.0.get() | mask } else { self.0.get() & !mask });
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+// the `size` bytes at `offset` into an 8-byte register, so that 32-bit
+// harts can access it in halves
+pub fn read_part(reg: u64, offset: u64, size: u64) -> u64 {
+    let value = reg >> (8 * offset);
+    if size == 8 { value } else { value & ((1 << (8 * size)) - 1) }
+}
+
+pub fn write_part(reg: u64, offset: u64, size: u64, value: u64) -> u64 {
+    let mask = if size == 8 { u64::MAX } else { (1 << (8 * size)) - 1 };
+    (reg & !(mask << (8 * offset))) | (value & mask) << (8 * offset)
+}
+
+struct Mapping {
+    start: u64,
+    size: u64,
+    device: Box<dyn Device>,
+}
+
+/// Physical address ranges claimed by devices.
+#[derive(Default)]
+pub struct Bus {
+    devices: Vec<Mapping>,
+    // interrupt requests into the hart, as mip bits
+    pub interrupts: Lines,
+    // mtime minus the cycle count, for the time CSR
+    pub time_offset: Rc<Cell<u64>>,
+}
+
+impl Bus {
+    pub fn attach(&mut self, start: u64, size: u64, device: Box<dyn Device>) {
+        assert!(self.devices.iter().all(|x| start + size <= x.start || x.start + x.size <= start),
+                "device at {:x} overlaps another one", start);
+        self.devices.push(Mapping { start, size, device });
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.devices.iter().any(|x| x.start <= address && address - x.start < x.size)
+    }
+
+    // the device holding all of [address, address + size)
+    fn find(&mut self, address: u64, size: u64) -> Option<(&mut Box<dyn Device>, u64)> {
+        self.devices.iter_mut()
+            .find(|x| x.start <= address && address - x.start + size <= x.size)
+            .map(|x| (&mut x.device, address - x.start))
+    }
+
+    pub fn read(&mut self, address: u64, buf: &mut [u8]) -> bool {
+        let size = buf.len() as u64;
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return false;
+        }
+        match self.find(address, size).and_then(|(device, offset)| device.read(offset, size)) {
+            Some(value) => {
+                buf.copy_from_slice(&value.to_le_bytes()[..buf.len()]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn write(&mut self, address: u64, buf: &[u8]) -> bool {
+        let size = buf.len() as u64;
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return false;
+        }
+        let mut bytes = [0; 8];
+        bytes[..buf.len()].copy_from_slice(buf);
+        match self.find(address, size) {
+            Some((device, offset)) => device.write(offset, size, u64::from_le_bytes(bytes)),
+            None => false,
+        }
+    }
+
+    pub fn tick(&mut self, now: u64) {
+        self.devices.iter_mut().for_each(|x| x.device.tick(now));
+    }
+
+    pub fn next_event(&self) -> Option<u64> {
+        self.devices.iter().filter_map(|x| x.device.next_event()).min()
+    }
+
+    // a 16550 UART, a CLINT and a PLIC where QEMU `virt` has them
+    pub fn attach_virt(&mut self, input: Box<dyn Read + Send>, output: Box<dyn Write>) {
+        let sources = Lines::default();
+        let hart = self.interrupts.clone();
+        // the UART ticks before the PLIC, which then sees its line
+        self.attach(UART_BASE, UART_SIZE,
+                    Box::new(Uart::new(input, output, sources.clone(), 1 << UART_IRQ)));
+        self.attach(CLINT_BASE, CLINT_SIZE, Box::new(Clint::new(hart.clone(), MIP_MSIP, MIP_MTIP, self.time_offset.clone())));
+        self.attach(PLIC_BASE, PLIC_SIZE,
+                    Box::new(Plic::new(sources, hart, [MIP_MEIP, MIP_SEIP])));
+    }
+}
+
+#[test]
+fn test_bus() {
+    let mut bus = Bus::default();
+    bus.attach_virt(Box::new(&b"a"[..]), Box::new(std::io::sink()));
+    assert!(bus.contains(CLINT_BASE + 0xbff8));
+    assert!(!bus.contains(CLINT_BASE + CLINT_SIZE));
+
+    // mtime follows the cycle count, and may be accessed in halves
+    bus.tick(0x1_0000_0005);
+    let mut buf = [0; 4];
+    assert!(bus.read(CLINT_BASE + 0xbffc, &mut buf));
+    assert_eq!(u32::from_le_bytes(buf), 1);
+    assert!(bus.write(CLINT_BASE + 0x4000, &5u64.to_le_bytes()));
+    bus.tick(0x1_0000_0006);
+    assert_eq!(bus.interrupts.get(), MIP_MTIP);
+
+    // received data raises UART_IRQ, which the PLIC forwards to M-mode
+    let word = |bus: &mut Bus, address: u64, value: u32| {
+        assert!(bus.write(address, &value.to_le_bytes()));
+    };
+    word(&mut bus, PLIC_BASE + 4 * UART_IRQ as u64, 1);
+    word(&mut bus, PLIC_BASE + 0x2000, 1 << UART_IRQ);
+    assert!(bus.write(UART_BASE + 1, &[1]));
+    while bus.interrupts.get() & MIP_MEIP == 0 {
+        bus.tick(0);
+    }
+    assert!(bus.read(PLIC_BASE + 0x20_0004, &mut buf));
+    assert_eq!(u32::from_le_bytes(buf), UART_IRQ);
+    let mut byte = [0];
+    assert!(bus.read(UART_BASE, &mut byte));
+    assert_eq!(&byte, b"a");
+    word(&mut bus, PLIC_BASE + 0x20_0004, UART_IRQ);
+    bus.tick(0);
+    assert_eq!(bus.interrupts.get() & MIP_MEIP, 0);
+
+    // unaligned to any register, or outside every device
+    assert!(!bus.read(PLIC_BASE + 0x20_0000 - 2, &mut buf));
+    assert!(!bus.read(0x3000_0000, &mut buf));
+}
@@ -0,0 +1,457 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+// fflags
+pub const NX: u64 = 1 << 0;
+pub const UF: u64 = 1 << 1;
+pub const OF: u64 = 1 << 2;
+pub const DZ: u64 = 1 << 3;
+pub const NV: u64 = 1 << 4;
+
+// rounding modes
+pub const RNE: u64 = 0;
+pub const RTZ: u64 = 1;
+pub const RDN: u64 = 2;
+pub const RUP: u64 = 3;
+pub const RMM: u64 = 4;
+pub const DYN: u64 = 7;
+
+/// The host only rounds to nearest-even, so every operation is computed
+/// that way first and then corrected with the sign of its exact error.
+pub trait Float: Copy + PartialOrd
+    + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self>
+    + Neg<Output=Self> {
+    const ZERO: Self;
+    const MAX: Self;
+    const MIN_POSITIVE: Self;
+    const BITS: u32;
+    const CANONICAL_NAN: u64;
+
+    fn to_bits64(self) -> u64;
+    fn from_bits64(bits: u64) -> Self;
+    fn from_i128(x: i128) -> Self;
+    fn to_i128(self) -> i128;
+    fn mul_add(self, a: Self, b: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn trunc(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn round_ties_even(self) -> Self;
+    fn is_nan(self) -> bool;
+    fn is_infinite(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+    fn is_subnormal(self) -> bool;
+
+    fn is_snan(self) -> bool {
+        // the quiet bit is the top bit of the fraction
+        self.is_nan() && self.to_bits64() & (Self::CANONICAL_NAN & !(Self::CANONICAL_NAN << 1)) == 0
+    }
+
+    fn canonical_nan() -> Self {
+        Self::from_bits64(Self::CANONICAL_NAN)
+    }
+}
+
+macro_rules! impl_float {
+    ($t:ty, $bits:ty, $nan:expr) => {
+        impl Float for $t {
+            const ZERO: Self = 0.0;
+            const MAX: Self = <$t>::MAX;
+            const MIN_POSITIVE: Self = <$t>::MIN_POSITIVE;
+            const BITS: u32 = <$bits>::BITS;
+            const CANONICAL_NAN: u64 = $nan;
+
+            fn to_bits64(self) -> u64 { self.to_bits() as u64 }
+            fn from_bits64(bits: u64) -> Self { <$t>::from_bits(bits as $bits) }
+            fn from_i128(x: i128) -> Self { x as $t }
+            fn to_i128(self) -> i128 { self as i128 }
+            fn mul_add(self, a: Self, b: Self) -> Self { <$t>::mul_add(self, a, b) }
+            fn sqrt(self) -> Self { <$t>::sqrt(self) }
+            fn abs(self) -> Self { <$t>::abs(self) }
+            fn trunc(self) -> Self { <$t>::trunc(self) }
+            fn floor(self) -> Self { <$t>::floor(self) }
+            fn ceil(self) -> Self { <$t>::ceil(self) }
+            fn round(self) -> Self { <$t>::round(self) }
+            fn round_ties_even(self) -> Self { <$t>::round_ties_even(self) }
+            fn is_nan(self) -> bool { <$t>::is_nan(self) }
+            fn is_infinite(self) -> bool { <$t>::is_infinite(self) }
+            fn is_sign_negative(self) -> bool { <$t>::is_sign_negative(self) }
+            fn is_subnormal(self) -> bool { <$t>::is_subnormal(self) }
+        }
+    };
+}
+
+impl_float!(f32, u32, 0x7fc0_0000);
+impl_float!(f64, u64, 0x7ff8_0000_0000_0000);
+
+fn next_up<F: Float>(x: F) -> F {
+    if x.is_nan() || (x.is_infinite() && !x.is_sign_negative()) {
+        return x;
+    }
+    if x == F::ZERO {
+        return F::from_bits64(1);
+    }
+    let bits = x.to_bits64();
+    F::from_bits64(if x > F::ZERO { bits + 1 } else { bits - 1 })
+}
+
+fn next_down<F: Float>(x: F) -> F {
+    -next_up(-x)
+}
+
+fn next_away<F: Float>(x: F) -> F {
+    if x.is_sign_negative() { next_down(x) } else { next_up(x) }
+}
+
+fn two_sum<F: Float>(a: F, b: F) -> (F, F) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+// the exact sign of a sum of floats, through a non-overlapping expansion
+fn exact_sign<F: Float>(terms: &[F]) -> Ordering {
+    let mut h: Vec<F> = Vec::new();
+    for &x in terms {
+        let mut q = x;
+        let mut next = Vec::new();
+        for &y in &h {
+            let (s, e) = two_sum(q, y);
+            if e != F::ZERO {
+                next.push(e);
+            }
+            q = s;
+        }
+        if q != F::ZERO {
+            next.push(q);
+        }
+        h = next;
+    }
+    match h.last() {
+        Some(x) if *x > F::ZERO => Ordering::Greater,
+        Some(_) => Ordering::Less,
+        None => Ordering::Equal,
+    }
+}
+
+// half the distance from q to the next float away from zero
+fn half_ulp<F: Float>(q: F) -> F {
+    (next_away(q) - q) / F::from_i128(2)
+}
+
+// `q` is the result rounded to nearest-even, `error` the sign of (exact - q)
+// and `tie` tells whether the exact result lies halfway to the next float
+fn round<F: Float>(q: F, error: Ordering, tie: impl FnOnce() -> bool,
+                   rm: u64, flags: &mut u64) -> F {
+    if q.is_infinite() {
+        *flags |= OF | NX;
+        let max = if q.is_sign_negative() { -F::MAX } else { F::MAX };
+        return match (rm, q.is_sign_negative()) {
+            (RTZ, _) | (RDN, false) | (RUP, true) => max,
+            _ => q,
+        };
+    }
+    if error == Ordering::Equal {
+        return q;
+    }
+    *flags |= NX;
+    let up = error == Ordering::Greater;
+    let step = match rm {
+        RTZ => q != F::ZERO && up == q.is_sign_negative(),
+        RDN => !up,
+        RUP => up,
+        RMM => up != q.is_sign_negative() && tie(),
+        RNE => false,
+        _ => unreachable!("invalid rounding mode {}", rm),
+    };
+    let r = if !step { q } else if up { next_up(q) } else { next_down(q) };
+    if r.is_infinite() {
+        *flags |= OF;
+    } else if r.abs() < F::MIN_POSITIVE {
+        *flags |= UF;
+    }
+    r
+}
+
+// NaN operands, invalid operations and infinite operands: the host result
+// is exact then, except that NaNs are made canonical
+fn special<F: Float>(operands: &[F], q: F, flags: &mut u64) -> Option<F> {
+    if operands.iter().any(|x| x.is_snan()) {
+        *flags |= NV;
+    }
+    if q.is_nan() {
+        if !operands.iter().any(|x| x.is_nan()) {
+            *flags |= NV;
+        }
+        return Some(F::canonical_nan());
+    }
+    if operands.iter().any(|x| x.is_infinite()) {
+        return Some(q);
+    }
+    None
+}
+
+// x + (-x) is -0 when rounding down and +0 otherwise
+fn exact_zero<F: Float>(q: F, negative: bool, positive: bool, rm: u64) -> F {
+    match (negative && positive, rm) {
+        (true, RDN) => -F::ZERO,
+        (true, _) => F::ZERO,
+        _ => q,
+    }
+}
+
+pub fn add<F: Float>(a: F, b: F, rm: u64, flags: &mut u64) -> F {
+    let q = a + b;
+    if let Some(x) = special(&[a, b], q, flags) {
+        return x;
+    }
+    let error = exact_sign(&[a, b, -q]);
+    if q == F::ZERO && error == Ordering::Equal {
+        let negative = a.is_sign_negative() || b.is_sign_negative();
+        let positive = !a.is_sign_negative() || !b.is_sign_negative();
+        return exact_zero(q, negative, positive, rm);
+    }
+    round(q, error, || exact_sign(&[a, b, -q, -half_ulp(q)]) == Ordering::Equal, rm, flags)
+}
+
+pub fn sub<F: Float>(a: F, b: F, rm: u64, flags: &mut u64) -> F {
+    add(a, -b, rm, flags)
+}
+
+pub fn mul<F: Float>(a: F, b: F, rm: u64, flags: &mut u64) -> F {
+    let q = a * b;
+    if let Some(x) = special(&[a, b], q, flags) {
+        return x;
+    }
+    let e = a.mul_add(b, -q);
+    round(q, exact_sign(&[e]), || exact_sign(&[e, -half_ulp(q)]) == Ordering::Equal, rm, flags)
+}
+
+pub fn div<F: Float>(a: F, b: F, rm: u64, flags: &mut u64) -> F {
+    let q = a / b;
+    if b == F::ZERO && !a.is_nan() && a != F::ZERO && !a.is_infinite() {
+        *flags |= DZ;
+        return q;
+    }
+    if let Some(x) = special(&[a, b], q, flags) {
+        return x;
+    }
+    // the remainder a - q * b is exact, and a quotient is never a tie
+    let r = (-q).mul_add(b, a);
+    let error = if b.is_sign_negative() { exact_sign(&[-r]) } else { exact_sign(&[r]) };
+    round(q, error, || false, rm, flags)
+}
+
+pub fn sqrt<F: Float>(a: F, rm: u64, flags: &mut u64) -> F {
+    let q = a.sqrt();
+    if let Some(x) = special(&[a], q, flags) {
+        return x;
+    }
+    let r = (-q).mul_add(q, a);
+    round(q, exact_sign(&[r]), || false, rm, flags)
+}
+
+// a * b + c with a single rounding
+pub fn fma<F: Float>(a: F, b: F, c: F, rm: u64, flags: &mut u64) -> F {
+    // inf * 0 is invalid even when c is a quiet NaN
+    if (a.is_infinite() && b == F::ZERO) || (a == F::ZERO && b.is_infinite()) {
+        *flags |= NV;
+        return F::canonical_nan();
+    }
+    let q = a.mul_add(b, c);
+    if let Some(x) = special(&[a, b, c], q, flags) {
+        return x;
+    }
+    let p = a * b;
+    let e = a.mul_add(b, -p);
+    let error = exact_sign(&[p, e, c, -q]);
+    if q == F::ZERO && error == Ordering::Equal {
+        let product = a.is_sign_negative() != b.is_sign_negative();
+        let negative = product || c.is_sign_negative();
+        let positive = !product || !c.is_sign_negative();
+        return exact_zero(q, negative, positive, rm);
+    }
+    round(q, error, || exact_sign(&[p, e, c, -q, -half_ulp(q)]) == Ordering::Equal, rm, flags)
+}
+
+pub fn min<F: Float>(a: F, b: F, flags: &mut u64) -> F {
+    if a.is_snan() || b.is_snan() {
+        *flags |= NV;
+    }
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => F::canonical_nan(),
+        (true, false) => b,
+        (false, true) => a,
+        _ if a < b || (a == b && a.is_sign_negative()) => a,
+        _ => b,
+    }
+}
+
+pub fn max<F: Float>(a: F, b: F, flags: &mut u64) -> F {
+    if a.is_snan() || b.is_snan() {
+        *flags |= NV;
+    }
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => F::canonical_nan(),
+        (true, false) => b,
+        (false, true) => a,
+        _ if a > b || (a == b && !a.is_sign_negative()) => a,
+        _ => b,
+    }
+}
+
+// FEQ is a quiet comparison, FLT and FLE signal on any NaN
+pub fn eq<F: Float>(a: F, b: F, flags: &mut u64) -> bool {
+    if a.is_snan() || b.is_snan() {
+        *flags |= NV;
+    }
+    a == b
+}
+
+pub fn lt<F: Float>(a: F, b: F, flags: &mut u64) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= NV;
+    }
+    a < b
+}
+
+pub fn le<F: Float>(a: F, b: F, flags: &mut u64) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= NV;
+    }
+    a <= b
+}
+
+fn sign_bit<F: Float>() -> u64 {
+    1 << (F::BITS - 1)
+}
+
+pub fn sgnj<F: Float>(a: F, b: F) -> F {
+    let s = sign_bit::<F>();
+    F::from_bits64((a.to_bits64() & !s) | (b.to_bits64() & s))
+}
+
+pub fn sgnjn<F: Float>(a: F, b: F) -> F {
+    let s = sign_bit::<F>();
+    F::from_bits64((a.to_bits64() & !s) | (!b.to_bits64() & s))
+}
+
+pub fn sgnjx<F: Float>(a: F, b: F) -> F {
+    F::from_bits64(a.to_bits64() ^ (b.to_bits64() & sign_bit::<F>()))
+}
+
+pub fn classify<F: Float>(a: F) -> u64 {
+    let negative = a.is_sign_negative();
+    let bit = if a.is_nan() {
+        if a.is_snan() { 8 } else { 9 }
+    } else if a.is_infinite() {
+        if negative { 0 } else { 7 }
+    } else if a == F::ZERO {
+        if negative { 3 } else { 4 }
+    } else if a.is_subnormal() {
+        if negative { 2 } else { 5 }
+    } else if negative { 1 } else { 6 };
+    1 << bit
+}
+
+// FCVT.{W,WU,L,LU}: saturates on overflow, NaN converts to the largest value
+pub fn to_int<F: Float>(a: F, signed: bool, bits: u32, rm: u64, flags: &mut u64) -> u64 {
+    let (lo, hi): (i128, i128) = if signed {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    } else {
+        (0, (1 << bits) - 1)
+    };
+    let r = match rm {
+        RTZ => a.trunc(),
+        RDN => a.floor(),
+        RUP => a.ceil(),
+        RMM => a.round(),
+        _ => a.round_ties_even(),
+    };
+    let v = if a.is_nan() {
+        *flags |= NV;
+        hi
+    } else if a.is_infinite() || r.to_i128() < lo || r.to_i128() > hi {
+        *flags |= NV;
+        if a.is_sign_negative() { lo } else { hi }
+    } else {
+        if r != a {
+            *flags |= NX;
+        }
+        r.to_i128()
+    };
+    // 32-bit results are sign-extended, even the unsigned ones
+    if bits == 32 { v as i32 as u64 } else { v as u64 }
+}
+
+// FCVT.{S,D}.{W,WU,L,LU}
+pub fn from_int<F: Float>(x: i128, rm: u64, flags: &mut u64) -> F {
+    let q = F::from_i128(x);
+    let back = q.to_i128();
+    let error = x.cmp(&back);
+    let tie = || (next_away(q).to_i128() - back) == 2 * (x - back);
+    round(q, error, tie, rm, flags)
+}
+
+// FCVT.S.D
+pub fn narrow(a: f64, rm: u64, flags: &mut u64) -> f32 {
+    let q = a as f32;
+    if let Some(x) = special(&[a], q as f64, flags) {
+        return if x.is_nan() { f32::canonical_nan() } else { q };
+    }
+    // both are close enough for the difference to be exact
+    let d = a - q as f64;
+    let tie = || d == (next_away(q) as f64 - q as f64) / 2.0;
+    round(q, exact_sign(&[d]), tie, rm, flags)
+}
+
+// FCVT.D.S
+pub fn widen(a: f32, flags: &mut u64) -> f64 {
+    if a.is_snan() {
+        *flags |= NV;
+    }
+    if a.is_nan() { f64::canonical_nan() } else { a as f64 }
+}
+
+#[test]
+fn test001() {
+    let mut flags = 0;
+    assert_eq!(add(1.0f32, 1e-10, RNE, &mut flags), 1.0);
+    assert_eq!(flags, NX);
+    assert_eq!(add(1.0f32, 1e-10, RUP, &mut flags), 1.000_000_1);
+    assert_eq!(add(-1.0f64, -1e-20, RTZ, &mut flags), -1.0);
+    assert_eq!(add(-1.0f64, -1e-20, RDN, &mut flags), -1.000_000_000_000_000_2);
+    assert!(add(1.0f64, -1.0, RDN, &mut flags).is_sign_negative());
+    assert!(!add(1.0f64, -1.0, RNE, &mut flags).is_sign_negative());
+    // 1 + 2^-24 is a tie in single precision
+    assert_eq!(add(1.0f32, 5.960_464_5e-8, RNE, &mut flags), 1.0);
+    assert_eq!(add(1.0f32, 5.960_464_5e-8, RMM, &mut flags), 1.000_000_1);
+
+    flags = 0;
+    assert_eq!(div(1.0f64, 3.0, RDN, &mut flags), 0.333_333_333_333_333_3);
+    assert_eq!(div(1.0f64, 3.0, RUP, &mut flags), 0.333_333_333_333_333_37);
+    assert_eq!(flags, NX);
+    assert_eq!(div(1.0f64, 0.0, RNE, &mut flags), f64::INFINITY);
+    assert_eq!(flags, NX | DZ);
+    assert_eq!(mul(f32::MAX, 2.0, RTZ, &mut flags), f32::MAX);
+    assert_eq!(flags, NX | DZ | OF);
+    assert_eq!(sqrt(-1.0f64, RNE, &mut flags).to_bits(), f64::CANONICAL_NAN);
+    assert_eq!(flags, NX | DZ | OF | NV);
+
+    flags = 0;
+    assert_eq!(to_int(-1.5f64, false, 32, RNE, &mut flags), 0);
+    assert_eq!(flags, NV);
+    assert_eq!(to_int(f32::NAN, true, 32, RNE, &mut flags), i32::MAX as u64);
+    assert_eq!(to_int(2.5f64, true, 64, RNE, &mut flags), 2);
+    assert_eq!(to_int(2.5f64, true, 64, RMM, &mut flags), 3);
+    assert_eq!(to_int(3e9f64, false, 32, RTZ, &mut flags), 3_000_000_000u32 as i32 as u64);
+    assert_eq!(from_int::<f32>(0x0100_0001, RNE, &mut flags), 16_777_216.0);
+    assert_eq!(from_int::<f32>(0x0100_0001, RUP, &mut flags), 16_777_218.0);
+    assert_eq!(narrow(1.0 + f64::EPSILON, RUP, &mut flags), 1.000_000_1);
+    assert_eq!(classify(-0.0f32), 1 << 3);
+    assert_eq!(classify(f64::from_bits(0x7ff0_0000_0000_0001)), 1 << 8);
+    assert_eq!(min(-0.0f32, 0.0, &mut flags).to_bits(), (-0.0f32).to_bits());
+    assert_eq!(max(f64::NAN, 1.0, &mut flags), 1.0);
+}
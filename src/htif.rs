@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+
+use crate::memory::Memory;
+use crate::syscall::ProxyKernel;
+
+const DEVICE_SYSCALL: u64 = 0;
+const DEVICE_CONSOLE: u64 = 1;
+const CONSOLE_PUTCHAR: u64 = 1;
+
+/// The host-target interface of Spike and the riscv-tests. The guest
+/// writes a command to `tohost`, which is cleared once it is done, and
+/// answers come back in `fromhost`.
+pub struct Htif {
+    pub tohost: u64,
+    pub fromhost: Option<u64>,
+}
+
+impl Htif {
+    // runs the command in tohost, if there is one
+    pub fn poll(&self, m: &mut Memory, kernel: &mut ProxyKernel) {
+        let command = match m.load_u64(self.tohost) {
+            Ok(0) | Err(_) => return,
+            Ok(x) => x,
+        };
+        let (device, cmd) = (command >> 56, (command >> 48) & 0xff);
+        let payload = command & 0xffff_ffff_ffff;
+        let _ = m.store_u64(self.tohost, 0);
+        match (device, cmd) {
+            (DEVICE_SYSCALL, 0) if payload & 1 != 0 => kernel.exit_code = Some(payload >> 1),
+            // the payload points to the call number and its arguments
+            (DEVICE_SYSCALL, 0) => {
+                let word = |m: &Memory, i: u64| m.load_u64(payload + 8 * i).unwrap_or(0);
+                let args = [1, 2, 3, 4].map(|i| word(m, i));
+                let result = kernel.syscall(m, word(m, 0), args);
+                let _ = m.store_u64(payload, result);
+                self.respond(m, device, cmd, 1);
+            }
+            (DEVICE_CONSOLE, CONSOLE_PUTCHAR) => {
+                let _ = io::stdout().write_all(&[payload as u8]);
+                let _ = io::stdout().flush();
+                self.respond(m, device, cmd, 0x100 | (payload & 0xff));
+            }
+            _ => eprintln!("unknown HTIF command {:x}", command),
+        }
+    }
+
+    fn respond(&self, m: &mut Memory, device: u64, cmd: u64, value: u64) {
+        if let Some(fromhost) = self.fromhost {
+            let _ = m.store_u64(fromhost, (device << 56) | (cmd << 48) | value);
+        }
+    }
+}
+
+#[test]
+fn test_htif() {
+    use crate::memory::PERM_RW;
+
+    let mut m = Memory::new();
+    m.alloc(0x1000, 0x100, PERM_RW);
+    let mut kernel = ProxyKernel::new(std::path::PathBuf::from("."));
+    let htif = Htif { tohost: 0x1000, fromhost: Some(0x1008) };
+
+    // getpid through the syscall proxy
+    m.store_u64(0x1080, 172).unwrap();
+    m.store_u64(0x1000, 0x1080).unwrap();
+    htif.poll(&mut m, &mut kernel);
+    assert_eq!(m.load_u64(0x1080), Ok(1));
+    assert_eq!(m.load_u64(0x1000), Ok(0));
+    assert_eq!(m.load_u64(0x1008), Ok(1));
+
+    m.store_u64(0x1000, (DEVICE_CONSOLE << 56) | (CONSOLE_PUTCHAR << 48) | b'\n' as u64).unwrap();
+    htif.poll(&mut m, &mut kernel);
+    assert_eq!(m.load_u64(0x1008), Ok((1 << 56) | (1 << 48) | 0x10a));
+
+    // what a failing riscv-test writes, with a 32-bit store
+    m.store_u32(0x1000, (3 << 1) | 1).unwrap();
+    htif.poll(&mut m, &mut kernel);
+    assert_eq!(kernel.exit_code, Some(3));
+}
@@ -1,19 +1,40 @@
-#![allow(non_snake_case)]
+#![allow(non_snake_case, clippy::unusual_byte_groupings, clippy::enum_variant_names)]
 
 use crate::register::Reg;
-use std::fmt::{Debug, Formatter, Error};
 
 const R_MASK: u32 =     0b_1111111_00000_00000_111_00000_1111111_u32;
 const I_MASK: u32 =     0b_0000000_00000_00000_111_00000_1111111_u32;
+// RV64 shift amounts are 6 bits wide, which leaves a funct6
+const H_MASK: u32 =     0b_1111110_00000_00000_111_00000_1111111_u32;
+// the *W shifts only take 5 bits, shamt[5] set is illegal
+const HW_MASK: u32 =    0b_1111111_00000_00000_111_00000_1111111_u32;
+// aq/rl are ignored: with a single hart every AMO is already ordered
+const A_MASK: u32 =     0b_1111100_00000_00000_111_00000_1111111_u32;
+// OP-FP: funct3 holds the rounding mode, and unary operations select
+// their variant with rs2
+const F_MASK: u32 =     0b_1111111_00000_00000_000_00000_1111111_u32;
+const FU_MASK: u32 =    0b_1111111_11111_00000_000_00000_1111111_u32;
+const FX_MASK: u32 =    0b_1111111_11111_00000_111_00000_1111111_u32;
+// fused multiply-add: rs3 and the rounding mode leave only the format
+const R4_MASK: u32 =    0b_0000011_00000_00000_000_00000_1111111_u32;
 const OP_MASK: u32 =    0b_0000000_00000_00000_000_00000_1111111_u32;
+const E_MASK: u32 =     0b_1111111_11111_11111_111_11111_1111111_u32;
 
 pub enum InstFormat {
     RFormat(u32, u32, u32),
     IFormat(u32, u32),
+    HFormat(u32, u32, u32),
+    HWFormat(u32, u32, u32),
+    AFormat(u32, u32, u32),
     SFormat(u32, u32),
     BFormat(u32, u32),
     UFormat(u32),
     JFormat(u32),
+    EFormat(u32, u32, u32),
+    FFormat(u32, u32),
+    FUFormat(u32, u32, u32),
+    FXFormat(u32, u32, u32, u32),
+    R4Format(u32, u32),
 }
 
 #[derive(Debug)]
@@ -56,6 +77,27 @@ pub struct JOperands {
     pub rd: Reg,
 }
 
+#[derive(Debug)]
+pub struct FOperands {
+    pub rm: u64,
+    pub rs2: Reg,
+    pub rs1: Reg,
+    pub rd: Reg,
+}
+
+#[derive(Debug)]
+pub struct R4Operands {
+    pub rm: u64,
+    pub rs3: Reg,
+    pub rs2: Reg,
+    pub rs1: Reg,
+    pub rd: Reg,
+}
+
+// SYSTEM instructions that are identified by the whole word (ECALL, EBREAK...)
+#[derive(Debug)]
+pub struct EOperands {}
+
 pub trait InstrMatch {
     fn is_match(&self, format: InstFormat) -> bool;
     fn decode_I(&self) -> IOperands;
@@ -64,6 +106,9 @@ pub trait InstrMatch {
     fn decode_B(&self) -> BOperands;
     fn decode_U(&self) -> UOperands;
     fn decode_J(&self) -> JOperands;
+    fn decode_E(&self) -> EOperands;
+    fn decode_F(&self) -> FOperands;
+    fn decode_R4(&self) -> R4Operands;
 }
 
 fn u32_bits(value: u32, from: u32, to: u32) -> u32 {
@@ -80,14 +125,16 @@ fn u32_inst_sign(value: u32, start: u32) -> u64 {
     ((((value as i32 >> 31) as i64) >> start as i64) as u64) << start as u64
 }
 
-fn u32_opcode(value: u32) -> u32 { u32_bits(value, 0, 7) }
-
 fn u32_rd(value: u32) -> Reg { Reg::from(u32_bits(value, 7, 12)) }
 
 fn u32_rs1(value: u32) -> Reg { Reg::from(u32_bits(value, 15, 20)) }
 
 fn u32_rs2(value: u32) -> Reg { Reg::from(u32_bits(value, 20, 25)) }
 
+fn u32_rs3(value: u32) -> Reg { Reg::from(u32_bits(value, 27, 32)) }
+
+fn u32_rm(value: u32) -> u64 { u32_bits(value, 12, 15) as u64 }
+
 fn u32_i_imm(value: u32) -> u64 {
     u32_inst_sign(value, 11)
         | u32_inst(value, 20, 31, 0)
@@ -129,6 +176,17 @@ fn test001() {
     assert_eq!(u32_b_imm(0xfae7d8e3), (-0x50i32) as u64)
 }
 
+#[test]
+fn test_shift_amounts() {
+    use crate::action::matching;
+    // slli and slliw a0, a0, 1, then with shamt[5] set
+    assert!(matching(0x00151513u32).is_some());
+    assert!(matching(0x02151513u32).is_some());
+    assert!(matching(0x0015151bu32).is_some());
+    assert!(matching(0x0215151bu32).is_none());
+    assert!(matching(0x4215551bu32).is_none());
+}
+
 impl InstrMatch for u32 {
     fn is_match(&self, format: InstFormat) -> bool {
         match format {
@@ -140,6 +198,18 @@ impl InstrMatch for u32 {
                 let v = (funct3 << 12) | op;
                 (self & I_MASK) == v
             }
+            InstFormat::HFormat(funct7, funct3, op) => {
+                let v = (funct7 << 25) | (funct3 << 12) | op;
+                (self & H_MASK) == v
+            }
+            InstFormat::HWFormat(funct7, funct3, op) => {
+                let v = (funct7 << 25) | (funct3 << 12) | op;
+                (self & HW_MASK) == v
+            }
+            InstFormat::AFormat(funct7, funct3, op) => {
+                let v = (funct7 << 25) | (funct3 << 12) | op;
+                (self & A_MASK) == v
+            }
             InstFormat::SFormat(funct3, op) => {
                 let v = (funct3 << 12) | op;
                 (self & I_MASK) == v
@@ -156,6 +226,26 @@ impl InstrMatch for u32 {
                 let v = op;
                 (self & OP_MASK) == v
             }
+            InstFormat::EFormat(funct12, funct3, op) => {
+                let v = (funct12 << 20) | (funct3 << 12) | op;
+                (self & E_MASK) == v
+            }
+            InstFormat::FFormat(funct7, op) => {
+                let v = (funct7 << 25) | op;
+                (self & F_MASK) == v
+            }
+            InstFormat::FUFormat(funct7, rs2, op) => {
+                let v = (funct7 << 25) | (rs2 << 20) | op;
+                (self & FU_MASK) == v
+            }
+            InstFormat::FXFormat(funct7, rs2, funct3, op) => {
+                let v = (funct7 << 25) | (rs2 << 20) | (funct3 << 12) | op;
+                (self & FX_MASK) == v
+            }
+            InstFormat::R4Format(funct7, op) => {
+                let v = (funct7 << 25) | op;
+                (self & R4_MASK) == v
+            }
         }
     }
 
@@ -204,5 +294,28 @@ impl InstrMatch for u32 {
             rd: u32_rd(*self),
         }
     }
+
+    fn decode_E(&self) -> EOperands {
+        EOperands {}
+    }
+
+    fn decode_F(&self) -> FOperands {
+        FOperands {
+            rm: u32_rm(*self),
+            rs2: u32_rs2(*self),
+            rs1: u32_rs1(*self),
+            rd: u32_rd(*self),
+        }
+    }
+
+    fn decode_R4(&self) -> R4Operands {
+        R4Operands {
+            rm: u32_rm(*self),
+            rs3: u32_rs3(*self),
+            rs2: u32_rs2(*self),
+            rs1: u32_rs1(*self),
+            rd: u32_rd(*self),
+        }
+    }
 }
 
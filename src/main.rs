@@ -1,34 +1,321 @@
 use std::{env, io};
-use objdump::Elf;
 use std::process::exit;
-use crate::simulator::Simulator;
-use std::io::{Error, BufReader, BufRead};
+use crate::simulator::{Simulator, LoadConfig, StartMode, VIRT_RAM_BASE, VIRT_RAM_SIZE};
+use std::io::{BufReader, BufRead};
 use std::fs::File;
+use std::path::PathBuf;
 use crate::cache::{CacheOp, Storage, CacheConfig};
+use crate::memory::Misaligned;
+use crate::pipeline::Forwarding;
+use crate::btb::{BtbConfig, TargetPredictor, DEFAULT_BTB, DEFAULT_RAS_DEPTH};
+use crate::predictor::{new_predictor, BranchPredictor, Perceptron, Tage, TageConfig};
+use crate::tlb::{Replacement, Tlb, TlbConfig};
 
 mod memory;
 mod simulator;
 mod register;
 mod instruction;
 mod action;
+mod btb;
 mod statistic;
 mod cache;
+mod syscall;
+mod csr;
+mod trap;
+mod fpu;
+mod compressed;
+mod compliance;
+mod device;
+mod htif;
+mod uart;
+mod clint;
+mod plic;
+mod mmu;
+mod pipeline;
+mod predictor;
+mod pmp;
+mod tlb;
+
+const PIPELINE_USAGE: &str = "pipeline [--root DIR] [--main] [--virt] [--diagram] [--env NAME=VALUE]... \
+[--stdin FILE] [--stack-top ADDR] [--stack-size SIZE] [--heap-size SIZE] [--ram BASE:SIZE] \
+[--misaligned emulate|trap|penalty:CYCLES] [--forwarding none|ex-ex|mem-ex|full] \
+[--predictor PREDICTOR] [--btb ENTRIES:WAYS] [--ras DEPTH] \
+[--itlb TLB] [--dtlb TLB] ELF [SYMBOL]... [-- ARG...]";
+
+const COMPLIANCE_USAGE: &str = "compliance [--references DIR] [--signatures DIR] \
+[--granularity 4|8] [--max-steps N] DIR";
+
+// removes `name value` from the arguments
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|s| s == name)?;
+    if i + 1 >= args.len() {
+        eprintln!("{} needs a value", name);
+        exit(1);
+    }
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+// 4096, 0x1000, 64K, 8M or 1G
+fn parse_size(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.chars().last()? {
+        'K' | 'k' => (&s[..s.len() - 1], 10),
+        'M' | 'm' => (&s[..s.len() - 1], 20),
+        'G' | 'g' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(1 << shift)
+}
+
+// BASE:SIZE, both as for parse_size
+fn parse_ram(s: &str) -> Option<(u64, u64)> {
+    let (base, size) = s.split_once(':')?;
+    let (base, size) = (parse_size(base)?, parse_size(size)?);
+    base.checked_add(size)?;
+    Some((base, size))
+}
+
+fn take_size(args: &mut Vec<String>, name: &str) -> Option<u64> {
+    take_option(args, name).map(|s| parse_size(&s).unwrap_or_else(|| {
+        eprintln!("{}: invalid size {}", name, s);
+        exit(1);
+    }))
+}
+
+fn parse_misaligned(s: &str) -> Option<Misaligned> {
+    match s {
+        "emulate" => Some(Misaligned::Emulate),
+        "trap" => Some(Misaligned::Trap),
+        _ => s.strip_prefix("penalty:")?.parse().ok().map(Misaligned::Penalty),
+    }
+}
+
+fn parse_forwarding(s: &str) -> Option<Forwarding> {
+    match s {
+        "none" => Some(Forwarding::None),
+        "ex-ex" => Some(Forwarding::ExEx),
+        "mem-ex" => Some(Forwarding::MemEx),
+        "full" => Some(Forwarding::Full),
+        _ => None,
+    }
+}
+
+// NAME[:BITS], where BITS sizes the tables of the dynamic predictors, and
+// then TABLES:MIN_HISTORY:MAX_HISTORY:TAG_BITS:RESET_PERIOD for tage or
+// TABLES:HISTORY for perceptron
+fn parse_predictor(s: &str) -> Option<Box<dyn BranchPredictor>> {
+    let mut fields = s.split(':');
+    let name = fields.next()?;
+    let numbers: Vec<u64> = fields.map(|x| x.parse().ok()).collect::<Option<_>>()?;
+    let bits = numbers.first().copied().unwrap_or(12);
+    if !(1..=20).contains(&bits) {
+        return None;
+    }
+    let bits = bits as u32;
+    match (name, &numbers[..]) {
+        ("tage", &[_, tables, min, max, tag_bits, reset_period]) => {
+            if !(1..=16).contains(&tables) || min == 0 || !(min..=4096).contains(&max)
+                || !(2..=16).contains(&tag_bits) || reset_period == 0 {
+                return None;
+            }
+            let config = TageConfig::geometric(bits, tables as usize, min as usize, max as usize,
+                                               tag_bits as u32, reset_period);
+            Some(Box::new(Tage::new(config)))
+        }
+        ("perceptron", &[_, tables, history]) => {
+            if !(1..=64).contains(&history) || !(1..=history).contains(&tables) {
+                return None;
+            }
+            Some(Box::new(Perceptron::new(bits, tables as u32, history as u32)))
+        }
+        (name, &[]) | (name, &[_]) => new_predictor(name, bits),
+        _ => None,
+    }
+}
+
+// ENTRIES:WAYS
+fn parse_btb(s: &str) -> Option<BtbConfig> {
+    let (entries, associativity) = s.split_once(':')?;
+    let entries: u64 = entries.parse().ok()?;
+    let associativity: u64 = associativity.parse().ok()?;
+    if associativity == 0 || !entries.is_multiple_of(associativity)
+        || !(entries / associativity).is_power_of_two() {
+        return None;
+    }
+    Some(BtbConfig { entries, associativity })
+}
+
+// ENTRIES:WAYS:lru|fifo|random:walk|CYCLES, where a miss either walks
+// the page table through the caches or costs a fixed number of cycles
+fn parse_tlb(name: &'static str, s: &str) -> Option<TlbConfig> {
+    let fields: Vec<&str> = s.split(':').collect();
+    if fields.len() != 4 {
+        return None;
+    }
+    let entries: u64 = fields[0].parse().ok()?;
+    let associativity: u64 = fields[1].parse().ok()?;
+    if associativity == 0 || !entries.is_multiple_of(associativity)
+        || !(entries / associativity).is_power_of_two() {
+        return None;
+    }
+    let replacement = match fields[2] {
+        "lru" => Replacement::Lru,
+        "fifo" => Replacement::Fifo,
+        "random" => Replacement::Random,
+        _ => return None,
+    };
+    let miss_penalty = match fields[3] {
+        "walk" => None,
+        cycles => Some(cycles.parse().ok()?),
+    };
+    Some(TlbConfig { name, entries, associativity, replacement, miss_penalty })
+}
+
+fn take_tlb(args: &mut Vec<String>, option: &str, name: &'static str) -> Option<TlbConfig> {
+    take_option(args, option).map(|s| parse_tlb(name, &s).unwrap_or_else(|| {
+        eprintln!("{}: invalid TLB {}", option, s);
+        exit(1);
+    }))
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|s| s == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
 
 fn lab2_pipeline(args: &[String]) {
-    if args.len() < 1 {
-        eprintln!("unknown filename");
+    let mut args = args.to_vec();
+    // everything after `--` belongs to the guest
+    let guest_args = match args.iter().position(|s| s == "--") {
+        Some(i) => args.split_off(i)[1..].to_vec(),
+        None => Vec::new(),
+    };
+    let root = take_option(&mut args, "--root");
+    let stdin = take_option(&mut args, "--stdin");
+    let mut env = Vec::new();
+    while let Some(var) = take_option(&mut args, "--env") {
+        env.push(var);
+    }
+    let stack_top = take_size(&mut args, "--stack-top");
+    let stack_size = take_size(&mut args, "--stack-size");
+    let heap_size = take_size(&mut args, "--heap-size");
+    let ram = take_option(&mut args, "--ram").map(|s| {
+        parse_ram(&s).unwrap_or_else(|| {
+            eprintln!("--ram: invalid region {}, expected BASE:SIZE", s);
+            exit(1);
+        })
+    });
+    let itlb = take_tlb(&mut args, "--itlb", "ITLB");
+    let dtlb = take_tlb(&mut args, "--dtlb", "DTLB");
+    let misaligned = take_option(&mut args, "--misaligned").map(|s| {
+        parse_misaligned(&s).unwrap_or_else(|| {
+            eprintln!("--misaligned: unknown policy {}", s);
+            exit(1);
+        })
+    });
+    let forwarding = take_option(&mut args, "--forwarding").map(|s| {
+        parse_forwarding(&s).unwrap_or_else(|| {
+            eprintln!("--forwarding: unknown bypass network {}", s);
+            exit(1);
+        })
+    });
+    let predictor = take_option(&mut args, "--predictor").map(|s| {
+        parse_predictor(&s).unwrap_or_else(|| {
+            eprintln!("--predictor: invalid predictor {}, expected \
+taken|not-taken|btfn|1bit|2bit|gshare|local|tournament|tage|perceptron[:BITS]", s);
+            exit(1);
+        })
+    });
+    let btb = take_option(&mut args, "--btb").map(|s| {
+        parse_btb(&s).unwrap_or_else(|| {
+            eprintln!("--btb: invalid BTB {}", s);
+            exit(1);
+        })
+    });
+    let ras = take_option(&mut args, "--ras").map(|s| {
+        s.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("--ras: invalid depth {}", s);
+            exit(1);
+        })
+    });
+    // call main directly instead of running the C runtime
+    let start = if take_flag(&mut args, "--main") { StartMode::Main } else { StartMode::Entry };
+    // a UART on stdin and stdout, a CLINT, a PLIC and RAM, as on QEMU `virt`
+    let virt = take_flag(&mut args, "--virt");
+    // print what every pipeline stage holds, cycle by cycle
+    let diagram = take_flag(&mut args, "--diagram");
+    if args.is_empty() {
+        eprintln!("usage: {}", PIPELINE_USAGE);
         exit(1);
     }
 
     let mut simulator = Simulator::new();
-    simulator.load_from_elf(args[0].as_str());
+    if let Some(root) = root {
+        simulator.kernel.root = PathBuf::from(root);
+    }
+    if let Some(config) = itlb {
+        simulator.itlb = Tlb::new(config);
+    }
+    if let Some(config) = dtlb {
+        simulator.dtlb = Tlb::new(config);
+    }
+    if let Some(policy) = misaligned {
+        simulator.memory.policy = policy;
+    }
+    simulator.pipeline.diagram = diagram;
+    if let Some(forwarding) = forwarding {
+        simulator.pipeline.forwarding = forwarding;
+    }
+    if let Some(predictor) = predictor {
+        simulator.predictor = predictor;
+    }
+    if btb.is_some() || ras.is_some() {
+        let (btb, ras) = (btb.unwrap_or(DEFAULT_BTB), ras.unwrap_or(DEFAULT_RAS_DEPTH));
+        simulator.targets = TargetPredictor::new(btb, ras);
+    }
+    if virt {
+        simulator.memory.bus_mut().attach_virt(Box::new(io::stdin()), Box::new(io::stdout()));
+    }
+    if let Some(stdin) = stdin {
+        match File::open(&stdin) {
+            Ok(f) => simulator.kernel.redirect_stdin(f),
+            Err(e) => {
+                eprintln!("can not open {}: {}", stdin, e);
+                exit(1);
+            }
+        }
+    }
+    let mut config = LoadConfig::new(&args[0]);
+    config.start = start;
+    config.args.extend(guest_args);
+    config.env = env;
+    config.stack_top = stack_top.unwrap_or(config.stack_top);
+    config.stack_size = stack_size.unwrap_or(config.stack_size);
+    config.heap_size = heap_size.unwrap_or(config.heap_size);
+    // QEMU `virt` always has DRAM, bare-metal images put their stack in it
+    config.ram = ram.or_else(|| virt.then_some((VIRT_RAM_BASE, VIRT_RAM_SIZE)));
+    if let Err(e) = simulator.load_from_elf(args[0].as_str(), &config) {
+        eprintln!("{}: {}", args[0], e);
+        exit(1);
+    }
     if args.len() == 1 {
         loop {
             let mut input = String::new();
             match io::stdin().read_line(&mut input) {
+                Ok(0) => break,
                 Ok(_) => {
                     match &input[..] {
                         "regs\n" => simulator.regs.println(),
+                        "fregs\n" => simulator.fregs.println(),
                         "s\n" => { simulator.run(); }
                         "mem\n" => {
                             let mut input_text = String::new();
@@ -52,11 +339,11 @@ fn lab2_pipeline(args: &[String]) {
     }
     while simulator.run() {}
 
-    args.iter().for_each(|s| {
+    args[1..].iter().for_each(|s| {
         let res = simulator.elf.symbol_entries.iter()
-            .filter(|x| {
+            .find(|x| {
                 x.0.contains(s)
-            }).next();
+            });
         match res {
             None => {
                 println!("cannot find {}", s);
@@ -69,10 +356,57 @@ fn lab2_pipeline(args: &[String]) {
     });
 
     simulator.stat.println();
+    println!("predictor: {} bits", simulator.predictor.storage_bits());
+    simulator.targets.output_stats();
+    simulator.itlb.output_stats();
+    simulator.dtlb.output_stats();
+    simulator.cache.output_stats();
+    if simulator.fault.is_some() {
+        exit(1);
+    }
+    if let Some(code) = simulator.kernel.exit_code {
+        exit(code as i32);
+    }
+}
+
+// runs prebuilt riscv-tests or riscv-arch-test ELFs, which report through
+// tohost and may leave a signature to compare
+fn run_compliance(args: &[String]) {
+    let mut args = args.to_vec();
+    let references = take_option(&mut args, "--references").map(PathBuf::from);
+    let signatures = take_option(&mut args, "--signatures").map(PathBuf::from);
+    let granularity = match take_option(&mut args, "--granularity").as_deref() {
+        None | Some("4") => 4,
+        Some("8") => 8,
+        Some(s) => {
+            eprintln!("--granularity: must be 4 or 8, not {}", s);
+            exit(1);
+        }
+    };
+    let max_steps = take_option(&mut args, "--max-steps").map_or(10_000_000, |s| {
+        s.parse().unwrap_or_else(|_| {
+            eprintln!("--max-steps: invalid number {}", s);
+            exit(1);
+        })
+    });
+    if args.len() != 1 {
+        eprintln!("usage: {}", COMPLIANCE_USAGE);
+        exit(1);
+    }
+    if let Some(dir) = &signatures {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("can not create {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+    let options = compliance::Options { max_steps, references, signatures, granularity };
+    if !compliance::run_suite(PathBuf::from(&args[0]).as_path(), &options) {
+        exit(1);
+    }
 }
 
-fn lab3_run(cache: &mut Box<dyn Storage>, filename: &String) -> cache::StorageStats {
-    let file = File::open(filename.as_str()).unwrap();
+fn lab3_run(cache: &mut Box<dyn Storage>, filename: &str) -> cache::StorageStats {
+    let file = File::open(filename).unwrap();
     let reader = BufReader::new(file);
 
     for line in reader.lines() {
@@ -98,7 +432,7 @@ fn lab3_run(cache: &mut Box<dyn Storage>, filename: &String) -> cache::StorageSt
 }
 
 fn lab3_cache(args: &[String]) {
-    if args.len() < 1 {
+    if args.is_empty() {
         eprintln!("unknown filename");
         exit(1);
     }
@@ -116,7 +450,7 @@ fn lab3_cache(args: &[String]) {
 }
 
 fn lab3_cache1(args: &[String]) {
-    if args.len() < 1 {
+    if args.is_empty() {
         eprintln!("unknown filename");
         exit(1);
     }
@@ -184,7 +518,7 @@ fn lab3_cache1(args: &[String]) {
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: {} [pipeline|cache|cache1]", args[0]);
+        eprintln!("Usage: {} [pipeline|compliance|cache|cache1]", args[0]);
         exit(1)
     }
 
@@ -193,8 +527,9 @@ fn main() {
         "cache" => lab3_cache(&args[2..]),
         "cache1" => lab3_cache1(&args[2..]),
         "pipeline" => lab2_pipeline(&args[2..]),
+        "compliance" => run_compliance(&args[2..]),
         _ => {
-            eprintln!("Usage: {} [pipeline|cache]", args[0]);
+            eprintln!("Usage: {} [pipeline|compliance|cache]", args[0]);
             exit(1);
         },
     }
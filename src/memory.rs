@@ -1,173 +1,335 @@
-use std::os::macos::raw::stat;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::io::Read;
 use byteorder::{ByteOrder, LittleEndian};
 
+use crate::device::Bus;
+use crate::trap::Exception;
+
+pub const PAGE_SIZE: u64 = 4096;
+
+// page permissions, the same bits as the ELF p_flags
+pub const PERM_X: u8 = 1;
+pub const PERM_W: u8 = 2;
+pub const PERM_R: u8 = 4;
+pub const PERM_RW: u8 = PERM_R | PERM_W;
+
+// a mapped page that has never been written reads as zeros
+const UNBACKED: usize = usize::MAX;
+
+type Frame = Box<[u8; PAGE_SIZE as usize]>;
+
+#[derive(Copy, Clone)]
+struct Page {
+    // index into `frames`, or UNBACKED
+    frame: usize,
+    perm: u8,
+}
+
+/// What to do with a load or store that is not naturally aligned.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Misaligned {
+    // split it up, free of charge
+    Emulate,
+    // raise an address-misaligned exception
+    Trap,
+    // split it up and charge this many extra cycles
+    Penalty(u64),
+}
+
+/// Sparse memory made of 4 KiB pages. Pages are mapped by `alloc` but
+/// only get a frame on their first store. Accesses to unmapped addresses
+/// go to the devices on the bus.
 pub struct Memory {
-    segments: Vec<MemorySegment>,
+    pages: HashMap<u64, Page>,
+    frames: Vec<Frame>,
+    // the last page looked up
+    last: Cell<(u64, Page)>,
+    pub policy: Misaligned,
+    // misaligned loads and stores so far, including trapped ones
+    misaligned: Cell<u64>,
+    // reading a device register may change it
+    bus: RefCell<Bus>,
+    // set by a store to any of the 8 bytes at `watch`
+    watch: Option<u64>,
+    watch_hit: bool,
+}
+
+fn page_number(address: u64) -> u64 {
+    address / PAGE_SIZE
 }
 
-pub struct MemorySegment {
-    start: u64,
-    array: Vec<u8>,
-    size: usize,
+fn page_offset(address: u64) -> usize {
+    (address % PAGE_SIZE) as usize
 }
 
-impl MemorySegment {
-    pub fn new(start: u64, size: usize) -> Self {
+impl Memory {
+    pub fn new() -> Self {
         Self {
-            start,
-            size,
-            array: vec![0; size],
+            pages: HashMap::new(),
+            frames: Vec::new(),
+            last: Cell::new((u64::MAX, Page { frame: UNBACKED, perm: 0 })),
+            policy: Misaligned::Emulate,
+            misaligned: Cell::new(0),
+            bus: RefCell::new(Bus::default()),
+            watch: None,
+            watch_hit: false,
         }
     }
 
-    fn end(&self) -> u64 {
-        self.start + (self.size as u64)
+    // maps every page overlapping [start, start + size), a page shared
+    // by two mappings gets the permissions of both
+    pub fn alloc(&mut self, start: u64, size: usize, perm: u8) {
+        if size == 0 {
+            return;
+        }
+        let last = start + size as u64 - 1;
+        for page in page_number(start)..=page_number(last) {
+            self.pages.entry(page)
+                .or_insert(Page { frame: UNBACKED, perm: 0 })
+                .perm |= perm;
+        }
+        self.last.set((u64::MAX, Page { frame: UNBACKED, perm: 0 }));
     }
 
-    fn contains(&self, address: u64) -> bool {
-        self.start <= address && address < self.end()
+    // fills mapped memory from `reader`, ignoring permissions
+    pub fn load_from<T>(&mut self, start: u64, reader: &mut T, size: usize)
+        where T: Read {
+        let mut buf = vec![0; size];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(self.write(start, &buf, 0));
     }
 
-    fn store_u8(&mut self, address: u64, value: u8) {
-        debug_assert!(self.contains(address));
-        let offset = address - self.start;
-        self.array[offset as usize] = value
+    fn page(&self, page: u64) -> Option<Page> {
+        let (last_page, last) = self.last.get();
+        if last_page == page {
+            return Some(last);
+        }
+        let rv = *self.pages.get(&page)?;
+        self.last.set((page, rv));
+        Some(rv)
     }
 
-    fn store_u16(&mut self, address: u64, value: u16) {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 1));
-        let offset = (address - self.start) as usize;
-        LittleEndian::write_u16(&mut self.array[offset..], value)
+    fn frame_mut(&mut self, page: u64) -> Option<&mut Frame> {
+        let mut rv = self.page(page)?;
+        if rv.frame == UNBACKED {
+            rv.frame = self.frames.len();
+            self.frames.push(Box::new([0; PAGE_SIZE as usize]));
+            self.pages.insert(page, rv);
+            self.last.set((page, rv));
+        }
+        Some(&mut self.frames[rv.frame])
     }
 
-    fn store_u32(&mut self, address: u64, value: u32) {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 3));
-        let offset = (address - self.start) as usize;
-        LittleEndian::write_u32(&mut self.array[offset..], value)
+    // every byte is mapped with at least `perm`
+    fn mapped(&self, address: u64, size: u64, perm: u8) -> bool {
+        if size == 0 {
+            return true;
+        }
+        let last = address.wrapping_add(size - 1);
+        if last < address {
+            return false;
+        }
+        (page_number(address)..=page_number(last))
+            .all(|page| self.page(page).is_some_and(|x| x.perm & perm == perm))
     }
 
-    fn store_u64(&mut self, address: u64, value: u64) {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 7));
-        let offset = (address - self.start) as usize;
-        LittleEndian::write_u64(&mut self.array[offset..], value)
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        self.bus.get_mut()
     }
 
-    fn load_u8(&self, address: u64) -> u8 {
-        debug_assert!(self.contains(address));
-        let offset = address - self.start;
-        self.array[offset as usize]
+    pub fn is_device(&self, address: u64) -> bool {
+        self.bus.borrow().contains(address)
     }
 
-    fn load_u16(&self, address: u64) -> u16 {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 1));
-        let offset = (address - self.start) as usize;
-        LittleEndian::read_u16(&self.array[offset..])
+    pub fn watch(&mut self, address: u64) {
+        self.watch = Some(address);
     }
 
-    fn load_u32(&self, address: u64) -> u32 {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 3));
-        let offset = (address - self.start) as usize;
-        LittleEndian::read_u32(&self.array[offset..])
+    // whether the watched word was stored to since the last call
+    pub fn take_watch_hit(&mut self) -> bool {
+        std::mem::take(&mut self.watch_hit)
     }
 
-    fn load_u64(&self, address: u64) -> u64 {
-        debug_assert!(self.contains(address));
-        debug_assert!(self.contains(address + 7));
-        let offset = (address - self.start) as usize;
-        LittleEndian::read_u64(&self.array[offset..])
+    // false if any byte is not mapped with `perm`, devices can not be executed
+    fn read(&self, address: u64, buf: &mut [u8], perm: u8) -> bool {
+        if buf.is_empty() {
+            return true;
+        }
+        if !self.mapped(address, buf.len() as u64, perm) {
+            return perm != PERM_X && self.bus.borrow_mut().read(address, buf);
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let current = address + done as u64;
+            let offset = page_offset(current);
+            let n = (PAGE_SIZE as usize - offset).min(buf.len() - done);
+            match self.page(page_number(current)).unwrap().frame {
+                UNBACKED => buf[done..done + n].fill(0),
+                frame =>
+                    buf[done..done + n].copy_from_slice(&self.frames[frame][offset..offset + n]),
+            }
+            done += n;
+        }
+        true
     }
 
-    pub fn load_from<T>(&mut self, reader: &mut T, size: usize)
-        where T: Read {
-        reader.read_exact(&mut self.array[..size]).unwrap();
+    // false, with nothing written, if any byte is not mapped with `perm`
+    fn write(&mut self, address: u64, buf: &[u8], perm: u8) -> bool {
+        if buf.is_empty() {
+            return true;
+        }
+        if !self.mapped(address, buf.len() as u64, perm) {
+            return self.bus.get_mut().write(address, buf);
+        }
+        if let Some(watch) = self.watch {
+            self.watch_hit |= address < watch + 8 && watch < address + buf.len() as u64;
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let current = address + done as u64;
+            let offset = page_offset(current);
+            let n = (PAGE_SIZE as usize - offset).min(buf.len() - done);
+            let frame = self.frame_mut(page_number(current)).unwrap();
+            frame[offset..offset + n].copy_from_slice(&buf[done..done + n]);
+            done += n;
+        }
+        true
     }
-}
 
-impl Memory {
-    pub fn new() -> Self {
-        Self {
-            segments: Vec::new(),
+    pub fn misaligned(&self) -> u64 {
+        self.misaligned.get()
+    }
+
+    // counts a misaligned access, which raises an exception under Misaligned::Trap
+    pub fn check_alignment(&self, address: u64, size: u64, store: bool) -> Result<(), Exception> {
+        if address & (size - 1) == 0 {
+            return Ok(());
+        }
+        self.misaligned.set(self.misaligned.get() + 1);
+        match (self.policy, store) {
+            (Misaligned::Trap, false) => Err(Exception::LoadAddressMisaligned(address)),
+            (Misaligned::Trap, true) => Err(Exception::StoreAddressMisaligned(address)),
+            _ => Ok(()),
         }
     }
 
-    pub fn push(&mut self, segment: MemorySegment) {
-        self.segments.push(segment);
+    // an access that is not all there. A misaligned one that gets this far
+    // is split up, so running off the end of memory is an access fault too
+    fn fault(&self, address: u64, store: bool) -> Exception {
+        match store {
+            false => Exception::LoadAccessFault(address),
+            true => Exception::StoreAccessFault(address),
+        }
     }
 
-    pub fn alloc(&mut self, start: u64, size: usize) {
-        self.segments.push(
-            MemorySegment::new(start, size)
-        )
+    pub fn store_u8(&mut self, address: u64, value: u8) -> Result<(), Exception> {
+        if self.write(address, &[value], PERM_W) {
+            Ok(())
+        } else {
+            Err(Exception::StoreAccessFault(address))
+        }
     }
 
-    pub fn store_u8(&mut self, address: u64, value: u8) {
-        self.segments.iter_mut()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .store_u8(address, value)
+    pub fn load_u8(&self, address: u64) -> Result<u8, Exception> {
+        let mut buf = [0; 1];
+        if self.read(address, &mut buf, PERM_R) {
+            Ok(buf[0])
+        } else {
+            Err(Exception::LoadAccessFault(address))
+        }
     }
 
-    pub fn load_u8(&mut self, address: u64) -> u8 {
-        self.segments.iter()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .load_u8(address)
+    pub fn store_u16(&mut self, address: u64, value: u16) -> Result<(), Exception> {
+        self.check_alignment(address, 2, true)?;
+        let mut buf = [0; 2];
+        LittleEndian::write_u16(&mut buf, value);
+        if self.write(address, &buf, PERM_W) {
+            Ok(())
+        } else {
+            Err(self.fault(address, true))
+        }
     }
 
-    pub fn store_u16(&mut self, address: u64, value: u16) {
-        self.segments.iter_mut()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .store_u16(address, value)
+    pub fn store_u32(&mut self, address: u64, value: u32) -> Result<(), Exception> {
+        self.check_alignment(address, 4, true)?;
+        let mut buf = [0; 4];
+        LittleEndian::write_u32(&mut buf, value);
+        if self.write(address, &buf, PERM_W) {
+            Ok(())
+        } else {
+            Err(self.fault(address, true))
+        }
     }
 
-    pub fn store_u64(&mut self, address: u64, value: u64) {
-        self.segments.iter_mut()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .store_u64(address, value)
+    pub fn store_u64(&mut self, address: u64, value: u64) -> Result<(), Exception> {
+        self.check_alignment(address, 8, true)?;
+        let mut buf = [0; 8];
+        LittleEndian::write_u64(&mut buf, value);
+        if self.write(address, &buf, PERM_W) {
+            Ok(())
+        } else {
+            Err(self.fault(address, true))
+        }
     }
 
-    pub fn load_u16(&self, address: u64) -> u16 {
-        self.segments.iter()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .load_u16(address)
+    pub fn load_u16(&self, address: u64) -> Result<u16, Exception> {
+        self.check_alignment(address, 2, false)?;
+        let mut buf = [0; 2];
+        if self.read(address, &mut buf, PERM_R) {
+            Ok(LittleEndian::read_u16(&buf))
+        } else {
+            Err(self.fault(address, false))
+        }
     }
 
-    pub fn store_u32(&mut self, address: u64, value: u32) {
-        self.segments.iter_mut()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .store_u32(address, value)
+    pub fn load_u32(&self, address: u64) -> Result<u32, Exception> {
+        self.check_alignment(address, 4, false)?;
+        let mut buf = [0; 4];
+        if self.read(address, &mut buf, PERM_R) {
+            Ok(LittleEndian::read_u32(&buf))
+        } else {
+            Err(self.fault(address, false))
+        }
     }
 
-    pub fn load_u32(&self, address: u64) -> u32 {
-        self.segments.iter()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .load_u32(address)
+    pub fn load_u64(&self, address: u64) -> Result<u64, Exception> {
+        self.check_alignment(address, 8, false)?;
+        let mut buf = [0; 8];
+        if self.read(address, &mut buf, PERM_R) {
+            Ok(LittleEndian::read_u64(&buf))
+        } else {
+            Err(self.fault(address, false))
+        }
     }
 
-    pub fn load_u64(&self, address: u64) -> u64 {
-        self.segments.iter()
-            .find(|x| x.contains(address))
-            .expect("invalid memory address")
-            .load_u64(address)
+    pub fn fetch_u16(&self, address: u64) -> Result<u16, Exception> {
+        let mut buf = [0; 2];
+        if self.read(address, &mut buf, PERM_X) {
+            Ok(LittleEndian::read_u16(&buf))
+        } else {
+            Err(Exception::InstructionAccessFault(address))
+        }
+    }
+
+    // for fetches through the page tables, which decide what is executable
+    // instead of the ELF segments. Devices still can not be executed
+    pub fn fetch_mapped_u16(&self, address: u64) -> Result<u16, Exception> {
+        let mut buf = [0; 2];
+        if self.mapped(address, 2, 0) && self.read(address, &mut buf, 0) {
+            Ok(LittleEndian::read_u16(&buf))
+        } else {
+            Err(Exception::InstructionAccessFault(address))
+        }
     }
 
     pub fn println(&self, address: u64, size: usize) {
-        let num = size / 4;
         let mut indent = 0;
         for offset in (0..size).step_by(4) {
-            let val = self.load_u32(address + offset as u64);
-            print!("{:0>8x}({})\t", val, val as i32);
+            match self.load_u32(address + offset as u64) {
+                Ok(val) => print!("{:0>8x}({})\t", val, val as i32),
+                Err(_) => print!("????????\t"),
+            }
             indent += 1;
             if indent == 4 {
                 indent = 0;
@@ -179,3 +341,40 @@ impl Memory {
         }
     }
 }
+
+#[test]
+fn test_sparse_memory() {
+    let mut m = Memory::new();
+    // a gigabyte apart, and only two frames are ever backed
+    m.alloc(0x1000, 1 << 30, PERM_RW);
+    m.alloc(0x80_0000_0000, 16, PERM_RW);
+    assert_eq!(m.load_u64(0x2000_0000), Ok(0));
+    m.store_u64(0x80_0000_0008, 7).unwrap();
+    assert_eq!(m.load_u64(0x80_0000_0008), Ok(7));
+    // straddles two pages
+    m.store_u32(0x1ffe, 0x1234_5678).unwrap();
+    assert_eq!(m.load_u32(0x1ffe), Ok(0x1234_5678));
+    assert_eq!(m.load_u16(0x2000), Ok(0x1234));
+    assert_eq!(m.frames.len(), 3);
+    // runs off the end of mapped memory
+    assert_eq!(m.load_u32(0x80_0000_0ffe), Err(Exception::LoadAccessFault(0x80_0000_0ffe)));
+    assert_eq!(m.store_u64(0x80_0000_1000, 0), Err(Exception::StoreAccessFault(0x80_0000_1000)));
+    assert_eq!(m.fetch_u16(0), Err(Exception::InstructionAccessFault(0)));
+}
+
+#[test]
+fn test_permissions() {
+    let mut m = Memory::new();
+    m.alloc(0x1000, 0x1000, PERM_R | PERM_X);
+    m.alloc(0x2000, 0x1000, PERM_RW);
+    m.load_from(0x1000, &mut &[0x13, 0x05][..], 2);
+    assert_eq!(m.fetch_u16(0x1000), Ok(0x0513));
+    assert_eq!(m.store_u16(0x1000, 0), Err(Exception::StoreAccessFault(0x1000)));
+    assert_eq!(m.fetch_u16(0x2000), Err(Exception::InstructionAccessFault(0x2000)));
+    // half of it is writable
+    assert_eq!(m.store_u32(0x1ffe, 0), Err(Exception::StoreAccessFault(0x1ffe)));
+    assert_eq!(m.load_u32(0x1ffe), Ok(0));
+    // a shared page gets both permissions
+    m.alloc(0x2000, 16, PERM_X);
+    assert_eq!(m.fetch_u16(0x2000), Ok(0));
+}
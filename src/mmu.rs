@@ -0,0 +1,350 @@
+use crate::csr::Privilege;
+use crate::memory::{Memory, PAGE_SIZE};
+use crate::pmp::{Pmp, PMP_R, PMP_W, PMP_X};
+use crate::trap::Exception;
+
+const PTE_V: u64 = 1 << 0;
+const PTE_R: u64 = 1 << 1;
+const PTE_W: u64 = 1 << 2;
+const PTE_X: u64 = 1 << 3;
+const PTE_U: u64 = 1 << 4;
+const PTE_A: u64 = 1 << 6;
+const PTE_D: u64 = 1 << 7;
+
+const PPN_MASK: u64 = (1 << 44) - 1;
+
+pub const SATP_SV39: u64 = 8;
+pub const SATP_SV48: u64 = 9;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Access {
+    Fetch,
+    Load,
+    Store,
+}
+
+impl Access {
+    fn page_fault(self, address: u64) -> Exception {
+        match self {
+            Access::Fetch => Exception::InstructionPageFault(address),
+            Access::Load => Exception::LoadPageFault(address),
+            Access::Store => Exception::StorePageFault(address),
+        }
+    }
+
+    fn access_fault(self, address: u64) -> Exception {
+        match self {
+            Access::Fetch => Exception::InstructionAccessFault(address),
+            Access::Load => Exception::LoadAccessFault(address),
+            Access::Store => Exception::StoreAccessFault(address),
+        }
+    }
+
+    fn pmp(self) -> u8 {
+        match self {
+            Access::Fetch => PMP_X,
+            Access::Load => PMP_R,
+            Access::Store => PMP_W,
+        }
+    }
+}
+
+/// The part of the CSR state that address translation depends on.
+#[derive(Copy, Clone)]
+pub struct Translation {
+    pub satp: u64,
+    // loads and stores use mstatus.MPP instead when MPRV is set
+    pub data: Privilege,
+    pub fetch: Privilege,
+    pub sum: bool,
+    pub mxr: bool,
+    pub pmp: Pmp,
+}
+
+/// A successful page table walk, for the TLB model.
+pub struct Walk {
+    // virtual page number
+    pub page: u64,
+    pub satp: u64,
+    // the entries read on the way
+    pub ptes: Vec<u64>,
+}
+
+/// Memory as the hart sees it. Addresses are translated through the
+/// Sv39/Sv48 page table in satp before reaching the physical `Memory`.
+pub struct Mmu<'a> {
+    memory: &'a mut Memory,
+    translation: Translation,
+    // physical address of the last load or store
+    pub paddr: Option<u64>,
+    pub walks: Vec<Walk>,
+}
+
+fn same_page(a: u64, b: u64) -> bool {
+    a / PAGE_SIZE == b / PAGE_SIZE
+}
+
+impl<'a> Mmu<'a> {
+    pub fn new(memory: &'a mut Memory, translation: Translation) -> Self {
+        Self {
+            memory,
+            translation,
+            paddr: None,
+            walks: Vec::new(),
+        }
+    }
+
+    fn privilege(&self, access: Access) -> Privilege {
+        match access {
+            Access::Fetch => self.translation.fetch,
+            _ => self.translation.data,
+        }
+    }
+
+    // PMP sits between the hart and physical memory
+    fn protect(&self, address: u64, paddr: u64, size: u64, access: Access) -> Result<(), Exception> {
+        let privilege = self.privilege(access);
+        if self.translation.pmp.check(paddr, size, privilege, access.pmp()) {
+            Ok(())
+        } else {
+            Err(access.access_fault(address))
+        }
+    }
+
+    fn levels(&self, access: Access) -> Option<u64> {
+        let privilege = self.privilege(access);
+        if privilege == Privilege::Machine {
+            return None;
+        }
+        match self.translation.satp >> 60 {
+            SATP_SV39 => Some(3),
+            SATP_SV48 => Some(4),
+            _ => None,
+        }
+    }
+
+    fn translate(&mut self, address: u64, access: Access) -> Result<u64, Exception> {
+        let levels = match self.levels(access) {
+            Some(levels) => levels,
+            None => return Ok(address),
+        };
+        let t = self.translation;
+        let privilege = self.privilege(access);
+        // the bits above the virtual address must copy its top bit
+        let shift = 64 - (12 + 9 * levels);
+        if ((address << shift) as i64 >> shift) as u64 != address {
+            return Err(access.page_fault(address));
+        }
+
+        let mut table = (t.satp & PPN_MASK) * PAGE_SIZE;
+        let mut ptes = Vec::new();
+        for level in (0..levels).rev() {
+            let vpn = (address >> (12 + 9 * level)) & 0x1ff;
+            let pte_address = table + vpn * 8;
+            ptes.push(pte_address);
+            // the walk itself is checked like an S-mode access
+            if !t.pmp.check(pte_address, 8, Privilege::Supervisor, PMP_R) {
+                return Err(access.access_fault(address));
+            }
+            let mut pte = self.memory.load_u64(pte_address)
+                .map_err(|_| access.access_fault(address))?;
+            if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) || pte >> 54 != 0 {
+                return Err(access.page_fault(address));
+            }
+            let ppn = (pte >> 10) & PPN_MASK;
+            if pte & (PTE_R | PTE_X) == 0 {
+                table = ppn * PAGE_SIZE;
+                continue;
+            }
+
+            let allowed = match access {
+                Access::Fetch => pte & PTE_X != 0,
+                Access::Load => pte & PTE_R != 0 || (t.mxr && pte & PTE_X != 0),
+                Access::Store => pte & PTE_W != 0,
+            };
+            let user = match privilege {
+                Privilege::User => pte & PTE_U != 0,
+                // S-mode may touch user pages with SUM, but never run them
+                _ => pte & PTE_U == 0 || (t.sum && access != Access::Fetch),
+            };
+            // a superpage must be aligned to its size
+            let offset = (1 << (12 + 9 * level)) - 1;
+            if !allowed || !user || (ppn * PAGE_SIZE) & offset != 0 {
+                return Err(access.page_fault(address));
+            }
+            let flags = if access == Access::Store { PTE_A | PTE_D } else { PTE_A };
+            if pte & flags != flags {
+                if !t.pmp.check(pte_address, 8, Privilege::Supervisor, PMP_W) {
+                    return Err(access.access_fault(address));
+                }
+                pte |= flags;
+                self.memory.store_u64(pte_address, pte)
+                    .map_err(|_| access.access_fault(address))?;
+            }
+            self.walks.push(Walk { page: address / PAGE_SIZE, satp: t.satp, ptes });
+            return Ok((ppn * PAGE_SIZE) | (address & offset));
+        }
+        Err(access.page_fault(address))
+    }
+
+    fn load(&mut self, address: u64, size: u64) -> Result<u64, Exception> {
+        let paddr = self.translate(address, Access::Load)?;
+        self.paddr = Some(paddr);
+        let last = address.wrapping_add(size - 1);
+        let m = &*self.memory;
+        let result = if self.levels(Access::Load).is_none() || same_page(address, last) {
+            self.protect(address, paddr, size, Access::Load)?;
+            match size {
+                1 => m.load_u8(paddr).map(u64::from),
+                2 => m.load_u16(paddr).map(u64::from),
+                4 => m.load_u32(paddr).map(u64::from),
+                _ => m.load_u64(paddr),
+            }
+        } else {
+            // the next virtual page may be anywhere
+            m.check_alignment(address, size, false)?;
+            let high = self.translate(last, Access::Load)? & !(PAGE_SIZE - 1);
+            let low_size = PAGE_SIZE - address % PAGE_SIZE;
+            self.protect(address, paddr, low_size, Access::Load)?;
+            self.protect(address, high, size - low_size, Access::Load)?;
+            let m = &*self.memory;
+            (0..size).rev().try_fold(0, |value, i| {
+                let byte = address.wrapping_add(i);
+                let pa = if same_page(address, byte) { paddr + i } else { high | (byte % PAGE_SIZE) };
+                m.load_u8(pa).map(|b| (value << 8) | b as u64)
+            })
+        };
+        result.map_err(|e| e.with_tval(address))
+    }
+
+    fn store(&mut self, address: u64, size: u64, value: u64) -> Result<(), Exception> {
+        let paddr = self.translate(address, Access::Store)?;
+        self.paddr = Some(paddr);
+        let last = address.wrapping_add(size - 1);
+        let result = if self.levels(Access::Store).is_none() || same_page(address, last) {
+            self.protect(address, paddr, size, Access::Store)?;
+            let m = &mut *self.memory;
+            match size {
+                1 => m.store_u8(paddr, value as u8),
+                2 => m.store_u16(paddr, value as u16),
+                4 => m.store_u32(paddr, value as u32),
+                _ => m.store_u64(paddr, value),
+            }
+        } else {
+            self.memory.check_alignment(address, size, true)?;
+            // both pages are translated before anything is written
+            let high = self.translate(last, Access::Store)? & !(PAGE_SIZE - 1);
+            let low_size = PAGE_SIZE - address % PAGE_SIZE;
+            self.protect(address, paddr, low_size, Access::Store)?;
+            self.protect(address, high, size - low_size, Access::Store)?;
+            let m = &mut *self.memory;
+            (0..size).try_for_each(|i| {
+                let byte = address.wrapping_add(i);
+                let pa = if same_page(address, byte) { paddr + i } else { high | (byte % PAGE_SIZE) };
+                m.store_u8(pa, (value >> (8 * i)) as u8)
+            })
+        };
+        result.map_err(|e| e.with_tval(address))
+    }
+
+    pub fn load_u8(&mut self, address: u64) -> Result<u8, Exception> {
+        self.load(address, 1).map(|x| x as u8)
+    }
+
+    pub fn load_u16(&mut self, address: u64) -> Result<u16, Exception> {
+        self.load(address, 2).map(|x| x as u16)
+    }
+
+    pub fn load_u32(&mut self, address: u64) -> Result<u32, Exception> {
+        self.load(address, 4).map(|x| x as u32)
+    }
+
+    pub fn load_u64(&mut self, address: u64) -> Result<u64, Exception> {
+        self.load(address, 8)
+    }
+
+    pub fn store_u8(&mut self, address: u64, value: u8) -> Result<(), Exception> {
+        self.store(address, 1, value as u64)
+    }
+
+    pub fn store_u16(&mut self, address: u64, value: u16) -> Result<(), Exception> {
+        self.store(address, 2, value as u64)
+    }
+
+    pub fn store_u32(&mut self, address: u64, value: u32) -> Result<(), Exception> {
+        self.store(address, 4, value as u64)
+    }
+
+    pub fn store_u64(&mut self, address: u64, value: u64) -> Result<(), Exception> {
+        self.store(address, 8, value)
+    }
+
+    // 2-byte aligned, so never crosses a page
+    pub fn fetch_u16(&mut self, address: u64) -> Result<u16, Exception> {
+        let paddr = self.translate(address, Access::Fetch)?;
+        self.protect(address, paddr, 2, Access::Fetch)?;
+        let result = match self.levels(Access::Fetch) {
+            // the PTE and PMP have already allowed it
+            Some(_) => self.memory.fetch_mapped_u16(paddr),
+            None => self.memory.fetch_u16(paddr),
+        };
+        result.map_err(|e| e.with_tval(address))
+    }
+}
+
+#[test]
+fn test_sv39() {
+    use crate::memory::{PERM_RW, PERM_X};
+
+    let mut memory = Memory::new();
+    memory.alloc(0x8000_0000, 0x10_0000, PERM_RW | PERM_X);
+    // root table at 0x8000_0000, next level at 0x8000_1000
+    let (root, table) = (0x8000_0000, 0x8000_1000);
+    let pte = |pa: u64, flags: u64| ((pa / PAGE_SIZE) << 10) | flags | PTE_V;
+    // va 0x0 -> next level table
+    memory.store_u64(root, pte(table, 0)).unwrap();
+    // va 0x4000_0000 -> 1 GiB superpage at 0x8000_0000, kernel RWX
+    memory.store_u64(root + 8, pte(0x8000_0000, PTE_R | PTE_W | PTE_X)).unwrap();
+    // va 0x20_0000 -> a misaligned 2 MiB superpage
+    memory.store_u64(table + 8, pte(0x8000_1000, PTE_R)).unwrap();
+    // va 0x0 -> 2 MiB superpage at 0x8000_0000, user read-only
+    memory.store_u64(table, pte(0x8000_0000, PTE_R | PTE_U)).unwrap();
+    memory.store_u64(0x8000_3000, 42).unwrap();
+
+    // PMP lets everything through
+    let mut pmp = Pmp::default();
+    pmp.write_addr(0, u64::MAX);
+    pmp.write_cfg(0, 0x1f);
+    let mut translation = Translation {
+        satp: SATP_SV39 << 60 | (root / PAGE_SIZE),
+        data: Privilege::Supervisor,
+        fetch: Privilege::Supervisor,
+        sum: false,
+        mxr: false,
+        pmp,
+    };
+    let mut mmu = Mmu::new(&mut memory, translation);
+    assert_eq!(mmu.load_u64(0x4000_3000), Ok(42));
+    assert_eq!(mmu.walks[0].ptes, vec![root + 8]);
+    assert_eq!(mmu.paddr, Some(0x8000_3000));
+    assert_eq!(mmu.store_u64(0x4000_3008, 7), Ok(()));
+    assert_eq!(mmu.fetch_u16(0x4000_3008), Ok(7));
+    // a page not executable by itself runs through an X PTE
+    mmu.memory.alloc(0x8010_0000, 4, PERM_RW);
+    mmu.memory.store_u16(0x8010_0000, 9).unwrap();
+    assert_eq!(mmu.fetch_u16(0x4010_0000), Ok(9));
+    assert_eq!(mmu.memory.fetch_u16(0x8010_0000), Err(Exception::InstructionAccessFault(0x8010_0000)));
+    // S-mode needs SUM for user pages
+    assert_eq!(mmu.load_u64(0x3000), Err(Exception::LoadPageFault(0x3000)));
+    assert_eq!(mmu.load_u64(0x20_0000), Err(Exception::LoadPageFault(0x20_0000)));
+    assert_eq!(mmu.load_u64(1 << 40), Err(Exception::LoadPageFault(1 << 40)));
+
+    translation.data = Privilege::User;
+    let mut mmu = Mmu::new(&mut memory, translation);
+    assert_eq!(mmu.load_u64(0x3000), Ok(42));
+    assert_eq!(mmu.store_u64(0x3000, 0), Err(Exception::StorePageFault(0x3000)));
+    assert_eq!(mmu.load_u64(0x4000_3000), Err(Exception::LoadPageFault(0x4000_3000)));
+    // the accessed and dirty bits were set on the way
+    assert_eq!(memory.load_u64(root + 8).unwrap() & (PTE_A | PTE_D), PTE_A | PTE_D);
+    assert_eq!(memory.load_u64(table).unwrap() & (PTE_A | PTE_D), PTE_A);
+}
@@ -0,0 +1,277 @@
+use crate::action::ExecuteInfo;
+use crate::statistic::Statistic;
+
+const IF: usize = 0;
+const ID: usize = 1;
+const EX: usize = 2;
+const MEM: usize = 3;
+const WB: usize = 4;
+const STAGES: usize = 5;
+const NAMES: [&str; STAGES] = ["IF", "ID", "EX", "MEM", "WB"];
+
+// the paths a result can take to EX before it is written back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Forwarding {
+    // operands are only read from the register file, in ID
+    None,
+    // from the EX/MEM latch, to the instruction right behind
+    ExEx,
+    // from the MEM/WB latch, which also covers loads
+    MemEx,
+    // both of them
+    Full,
+}
+
+impl Forwarding {
+    fn ex_ex(self) -> bool {
+        matches!(self, Forwarding::ExEx | Forwarding::Full)
+    }
+
+    fn mem_ex(self) -> bool {
+        matches!(self, Forwarding::MemEx | Forwarding::Full)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    pc: u64,
+    info: ExecuteInfo,
+    // cycles it still has to spend in its stage
+    remaining: u64,
+    // fetch waits until it leaves EX
+    redirect: bool,
+    // counted as a data hazard already
+    hazard: bool,
+}
+
+/// The timing of a classic in-order IF/ID/EX/MEM/WB pipeline, advanced one
+/// cycle at a time. Instructions have already been executed by
+/// `action::execute` when they are fetched here, this only decides when
+/// each of them moves from one stage latch to the next.
+pub struct Pipeline {
+    // the latch in front of each stage
+    stages: [Option<Slot>; STAGES],
+    pub forwarding: Forwarding,
+    // print the occupancy of every cycle
+    pub diagram: bool,
+}
+
+fn latency(stage: usize, info: &ExecuteInfo) -> u64 {
+    match stage {
+        EX => info.exe_cycles.max(1),
+        MEM => info.mem_access.max(1),
+        _ => 1,
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            stages: [None; STAGES],
+            forwarding: Forwarding::Full,
+            diagram: false,
+        }
+    }
+
+    // whether the instruction in ID would enter EX without one of its
+    // operands, the ones ahead of it have already moved on this cycle
+    fn data_hazard(&self, slot: &Slot) -> bool {
+        slot.info.reg_read.iter().filter(|x| x.not_zero()).any(|reg| {
+            // only the youngest writer of a register matters
+            let producer = [EX, MEM, WB].iter().find_map(|&stage| {
+                self.stages[stage].filter(|x| x.info.reg_write == *reg).map(|x| (stage, x))
+            });
+            match producer {
+                None => false,
+                // still computing it, MUL and DIV for several cycles
+                Some((EX, _)) => true,
+                // loads only have it at the end of MEM
+                Some((MEM, x)) => x.info.load_reg.not_zero() || !self.forwarding.ex_ex(),
+                // written back while this one waits in ID
+                Some(_) => !self.forwarding.mem_ex(),
+            }
+        })
+    }
+
+    // a redirect in flight, fetch does not know where to go yet
+    fn fetch_held(&self) -> bool {
+        self.stages[IF..=EX].iter().flatten().any(|x| x.redirect)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.stages.iter().all(|x| x.is_none())
+    }
+
+    fn tick(&mut self, stat: &mut Statistic) {
+        stat.cycle += 1;
+        for slot in self.stages.iter_mut().flatten() {
+            slot.remaining = slot.remaining.saturating_sub(1);
+        }
+        let occupancy = self.stages;
+        let mut stalled = [false; STAGES];
+
+        // the oldest first, so that a latch freed this cycle can be refilled
+        self.stages[WB] = None;
+        for stage in (IF..WB).rev() {
+            let mut slot = match self.stages[stage] {
+                Some(x) => x,
+                None => continue,
+            };
+            if slot.remaining > 0 {
+                continue;
+            }
+            let hazard = stage == ID && self.data_hazard(&slot);
+            if hazard {
+                stat.num_data_stall += 1;
+                if !slot.hazard {
+                    slot.hazard = true;
+                    stat.num_data_hazard += 1;
+                }
+            }
+            if hazard || self.stages[stage + 1].is_some() {
+                stalled[stage] = true;
+                self.stages[stage] = Some(slot);
+                continue;
+            }
+            slot.remaining = latency(stage + 1, &slot.info);
+            self.stages[stage + 1] = Some(slot);
+            self.stages[stage] = None;
+        }
+
+        if self.diagram {
+            let cells: Vec<String> = occupancy.iter().zip(&stalled).map(|(slot, stalled)| {
+                match slot {
+                    Some(x) => format!("{:>9x}{}", x.pc, if *stalled { "*" } else { " " }),
+                    None => format!("{:>9} ", "-"),
+                }
+            }).collect();
+            println!("{:>8} {}", stat.cycle, cells.join(""));
+        }
+    }
+
+    // fetches an instruction as soon as IF is free, `fetch_stall` is how much
+    // longer than a cycle its fetch takes, and `redirect` that the next one
+    // can not be fetched until it resolves in EX
+    pub fn issue(&mut self, pc: u64, info: ExecuteInfo, fetch_stall: u64, redirect: bool,
+                 stat: &mut Statistic) {
+        if self.diagram && stat.cycle == 0 && self.is_empty() {
+            let names: Vec<String> = NAMES.iter().map(|x| format!("{:>9} ", x)).collect();
+            println!("{:>8} {}", "cycle", names.join(""));
+        }
+        while self.stages[IF].is_some() || self.fetch_held() {
+            if self.stages[IF].is_none() {
+                stat.num_control_stall += 1;
+            }
+            self.tick(stat);
+        }
+        self.stages[IF] = Some(Slot {
+            pc,
+            info,
+            remaining: 1 + fetch_stall,
+            redirect,
+            hazard: false,
+        });
+    }
+
+    // a trap or interrupt, the handler is fetched once the youngest
+    // instruction leaves EX
+    pub fn redirect(&mut self) {
+        if let Some(slot) = self.stages.iter_mut().flatten().next() {
+            slot.redirect = true;
+        }
+    }
+
+    pub fn drain(&mut self, stat: &mut Statistic) {
+        while !self.is_empty() {
+            self.tick(stat);
+        }
+    }
+
+    // sleeps until `cycle`, once everything in flight is done
+    pub fn skip_to(&mut self, cycle: u64, stat: &mut Statistic) {
+        self.drain(stat);
+        stat.cycle = stat.cycle.max(cycle);
+    }
+}
+
+#[test]
+fn test_pipeline() {
+    use crate::register::Reg;
+
+    let alu = ExecuteInfo { exe_cycles: 1, ..Default::default() };
+    let run = |forwarding, infos: &[(ExecuteInfo, bool)]| {
+        let mut pipeline = Pipeline::new();
+        pipeline.forwarding = forwarding;
+        let mut stat = Statistic::default();
+        for (i, (info, redirect)) in infos.iter().enumerate() {
+            pipeline.issue(4 * i as u64, *info, 0, *redirect, &mut stat);
+        }
+        pipeline.drain(&mut stat);
+        (stat.cycle, stat.num_data_stall)
+    };
+
+    // filling and draining five stages
+    assert_eq!(run(Forwarding::Full, &[(alu, false); 4]), (8, 0));
+
+    // a divide holds EX and everything behind it
+    let div = ExecuteInfo { exe_cycles: 10, ..alu };
+    assert_eq!(run(Forwarding::Full, &[(div, false), (alu, false)]), (15, 0));
+
+    // a mispredicted branch costs two bubbles
+    assert_eq!(run(Forwarding::Full, &[(alu, true), (alu, false)]), (8, 0));
+
+    // stalls for a result needed right away, and one instruction later, a
+    // multiply keeps the one right behind it waiting in ID
+    let (a0, zero) = (Reg::from(10), Reg::from(0));
+    let user = ExecuteInfo { reg_read: [a0, zero, zero], ..alu };
+    let producer = ExecuteInfo { reg_write: a0, ..alu };
+    let load = ExecuteInfo { mem_access: 1, load_reg: a0, ..producer };
+    let mul = ExecuteInfo { exe_cycles: 5, ..producer };
+    let stalls = |forwarding| {
+        [producer, load, mul].map(|x| {
+            let next = run(forwarding, &[(x, false), (user, false)]).1;
+            let after = run(forwarding, &[(x, false), (alu, false), (user, false)]).1;
+            (next, after)
+        })
+    };
+    assert_eq!(stalls(Forwarding::None), [(2, 1), (2, 1), (6, 1)]);
+    assert_eq!(stalls(Forwarding::ExEx), [(0, 1), (2, 1), (4, 1)]);
+    assert_eq!(stalls(Forwarding::MemEx), [(1, 0), (1, 0), (5, 0)]);
+    assert_eq!(stalls(Forwarding::Full), [(0, 0), (1, 0), (4, 0)]);
+    // only the youngest writer counts
+    assert_eq!(run(Forwarding::None, &[(load, false), (producer, false), (user, false)]).1, 2);
+
+    // what the generated executor reports, FP registers are not integer ones
+    let mut sim = crate::simulator::Simulator::new();
+    let mut info = |code: u32| {
+        let inst = crate::action::matching(code).unwrap();
+        crate::action::execute(&mut sim, inst, 4).unwrap()
+    };
+    let fadd_fa0 = info(0x02c5_8553);       // fadd.d fa0, fa1, fa2
+    let addi_a0 = info(0x0015_0513);        // addi a0, a0, 1
+    let fadd_ft0 = info(0x02c5_8053);       // fadd.d ft0, fa1, fa2
+    let read_ft0 = info(0x0200_0553);       // fadd.d fa0, ft0, ft0
+    let fadd_fa3 = info(0x02c5_86d3);       // fadd.d fa3, fa1, fa2
+    let fmadd = info(0x6ac5_8543);          // fmadd.d fa0, fa1, fa2, fa3
+    let csrrwi = info(0x0015_5073);         // csrrwi zero, fflags, 10
+    let stalls = |x, y| run(Forwarding::None, &[(x, false), (y, false)]).1;
+    assert_eq!(stalls(fadd_fa0, addi_a0), 0);
+    assert!(stalls(fadd_ft0, read_ft0) > 0);
+    assert!(stalls(fadd_fa3, fmadd) > 0);
+    assert_eq!(stalls(addi_a0, csrrwi), 0);
+
+    // FP loads are load-use hazards for FP consumers only
+    sim.memory.alloc(0x1000, 0x100, crate::memory::PERM_RW);
+    sim.regs.set_by_name("a1", 0x1000);
+    let mut info = |code: u32| {
+        let inst = crate::action::matching(code).unwrap();
+        crate::action::execute(&mut sim, inst, 4).unwrap()
+    };
+    let fld_ft0 = info(0x0005_b007);        // fld ft0, 0(a1)
+    let fld_fa0 = info(0x0005_b507);        // fld fa0, 0(a1)
+    let addi_a0 = info(0x0015_0513);
+    let read_ft0 = info(0x0200_0553);
+    let stalls = |x, y| run(Forwarding::Full, &[(x, false), (y, false)]).1;
+    assert!(stalls(fld_ft0, read_ft0) > 0);
+    assert_eq!(stalls(fld_fa0, addi_a0), 0);
+}
@@ -0,0 +1,142 @@
+use crate::device::{Device, Lines};
+
+// source 0 does not exist
+const SOURCES: usize = 64;
+// M-mode and S-mode of a single hart
+const CONTEXTS: usize = 2;
+
+const PRIORITY: u64 = 0x0;
+const PENDING: u64 = 0x1000;
+const ENABLE: u64 = 0x2000;
+const ENABLE_STRIDE: u64 = 0x80;
+const CONTEXT: u64 = 0x20_0000;
+const CONTEXT_STRIDE: u64 = 0x1000;
+
+/// A platform-level interrupt controller, laid out like the SiFive one.
+/// Sources are level triggered, and not pending again until completed.
+pub struct Plic {
+    priority: [u32; SOURCES],
+    pending: u64,
+    // claimed but not completed yet
+    claimed: u64,
+    enable: [u64; CONTEXTS],
+    threshold: [u32; CONTEXTS],
+    sources: Lines,
+    irq: Lines,
+    // the line each context raises
+    outputs: [u64; CONTEXTS],
+}
+
+impl Plic {
+    pub fn new(sources: Lines, irq: Lines, outputs: [u64; CONTEXTS]) -> Self {
+        Self {
+            priority: [0; SOURCES],
+            pending: 0,
+            claimed: 0,
+            enable: [0; CONTEXTS],
+            threshold: [0; CONTEXTS],
+            sources,
+            irq,
+            outputs,
+        }
+    }
+
+    // the pending source with the highest priority above the threshold,
+    // the lowest numbered one wins a tie
+    fn best(&self, context: usize) -> Option<usize> {
+        (1..SOURCES)
+            .filter(|&i| (self.pending & self.enable[context]) >> i & 1 != 0)
+            .filter(|&i| self.priority[i] > self.threshold[context])
+            .max_by_key(|&i| (self.priority[i], SOURCES - i))
+    }
+
+    fn update(&self) {
+        for context in 0..CONTEXTS {
+            self.irq.set(self.outputs[context], self.best(context).is_some());
+        }
+    }
+
+    fn claim(&mut self, context: usize) -> u64 {
+        match self.best(context) {
+            Some(i) => {
+                self.pending &= !(1 << i);
+                self.claimed |= 1 << i;
+                self.update();
+                i as u64
+            }
+            None => 0,
+        }
+    }
+}
+
+impl Device for Plic {
+    fn read(&mut self, offset: u64, size: u64) -> Option<u64> {
+        if size != 4 || !offset.is_multiple_of(4) {
+            return None;
+        }
+        // 32 sources to a word
+        let word = |bits: u64, offset: u64| {
+            bits.checked_shr(8 * offset as u32).unwrap_or(0) & 0xffff_ffff
+        };
+        // reads past the implemented sources and contexts are zero
+        let value = match offset {
+            PRIORITY..PENDING => self.priority.get((offset / 4) as usize).map_or(0, |&x| x as u64),
+            PENDING..ENABLE => word(self.pending, offset - PENDING),
+            ENABLE..CONTEXT => {
+                let context = ((offset - ENABLE) / ENABLE_STRIDE) as usize;
+                self.enable.get(context).map_or(0, |&x| word(x, (offset - ENABLE) % ENABLE_STRIDE))
+            }
+            _ => {
+                let context = ((offset - CONTEXT) / CONTEXT_STRIDE) as usize;
+                match (offset - CONTEXT) % CONTEXT_STRIDE {
+                    _ if context >= CONTEXTS => 0,
+                    0 => self.threshold[context] as u64,
+                    4 => self.claim(context),
+                    _ => 0,
+                }
+            }
+        };
+        Some(value)
+    }
+
+    fn write(&mut self, offset: u64, size: u64, value: u64) -> bool {
+        if size != 4 || !offset.is_multiple_of(4) {
+            return false;
+        }
+        let value = value & 0xffff_ffff;
+        match offset {
+            PRIORITY..PENDING => {
+                if let Some(x) = self.priority.get_mut((offset / 4) as usize) {
+                    *x = value as u32 & 7;
+                }
+            }
+            PENDING..ENABLE => {}
+            ENABLE..CONTEXT => {
+                let context = ((offset - ENABLE) / ENABLE_STRIDE) as usize;
+                let shift = 8 * ((offset - ENABLE) % ENABLE_STRIDE);
+                if let (Some(x), true) = (self.enable.get_mut(context), shift < 64) {
+                    // source 0 can not be enabled
+                    *x = (*x & !(0xffff_ffff << shift) | value << shift) & !1;
+                }
+            }
+            _ => {
+                let context = ((offset - CONTEXT) / CONTEXT_STRIDE) as usize;
+                match (offset - CONTEXT) % CONTEXT_STRIDE {
+                    0 if context < CONTEXTS => self.threshold[context] = value as u32 & 7,
+                    // completing a source this context does not enable is ignored
+                    4 if context < CONTEXTS && value < SOURCES as u64
+                        && self.enable[context] >> value & 1 != 0 =>
+                        self.claimed &= !(1 << value),
+                    _ => {}
+                }
+            }
+        }
+        self.update();
+        true
+    }
+
+    fn tick(&mut self, _now: u64) {
+        self.pending |= self.sources.get() & !self.claimed & !1;
+        self.update();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::csr::Privilege;
+
+pub const PMP_ENTRIES: usize = 16;
+
+// pmpcfg fields
+pub const PMP_R: u8 = 1 << 0;
+pub const PMP_W: u8 = 1 << 1;
+pub const PMP_X: u8 = 1 << 2;
+const PMP_A: u8 = 0b11 << 3;
+const PMP_L: u8 = 1 << 7;
+const PMP_RESERVED: u8 = 0b11 << 5;
+
+const A_OFF: u8 = 0;
+const A_TOR: u8 = 1;
+const A_NA4: u8 = 2;
+
+// pmpaddr holds bits 55:2 of the address
+const ADDR_MASK: u64 = (1 << 54) - 1;
+
+fn mode(cfg: u8) -> u8 {
+    (cfg & PMP_A) >> 3
+}
+
+/// Physical memory protection: 16 regions, each with its own permissions,
+/// that U/S-mode accesses must fall into. A locked region binds M-mode too.
+#[derive(Copy, Clone, Default)]
+pub struct Pmp {
+    cfg: [u8; PMP_ENTRIES],
+    addr: [u64; PMP_ENTRIES],
+}
+
+impl Pmp {
+    // `reg` is 0 for pmpcfg0 and 1 for pmpcfg2, which hold 8 entries each
+    pub fn read_cfg(&self, reg: usize) -> u64 {
+        (0..8).fold(0, |value, i| value | (self.cfg[reg * 8 + i] as u64) << (8 * i))
+    }
+
+    pub fn write_cfg(&mut self, reg: usize, value: u64) {
+        for i in 0..8 {
+            let entry = reg * 8 + i;
+            if self.cfg[entry] & PMP_L != 0 {
+                continue;
+            }
+            let mut cfg = (value >> (8 * i)) as u8 & !PMP_RESERVED;
+            // write-only is reserved
+            if cfg & (PMP_R | PMP_W) == PMP_W {
+                cfg &= !PMP_W;
+            }
+            self.cfg[entry] = cfg;
+        }
+    }
+
+    pub fn read_addr(&self, entry: usize) -> u64 {
+        self.addr[entry]
+    }
+
+    pub fn write_addr(&mut self, entry: usize, value: u64) {
+        // a locked TOR entry also locks the address below it
+        let next = self.cfg.get(entry + 1).copied().unwrap_or(0);
+        let tor_locked = next & PMP_L != 0 && mode(next) == A_TOR;
+        if self.cfg[entry] & PMP_L == 0 && !tor_locked {
+            self.addr[entry] = value & ADDR_MASK;
+        }
+    }
+
+    // the addresses an entry covers, as [start, end)
+    fn range(&self, entry: usize) -> Option<(u64, u128)> {
+        let addr = self.addr[entry];
+        match mode(self.cfg[entry]) {
+            A_OFF => None,
+            A_TOR => {
+                let start = if entry == 0 { 0 } else { self.addr[entry - 1] << 2 };
+                Some((start, (addr as u128) << 2))
+            }
+            A_NA4 => Some((addr << 2, ((addr as u128) << 2) + 4)),
+            // NAPOT: the trailing ones encode the size
+            _ => {
+                let size = 1u128 << (addr.trailing_ones() + 3);
+                let start = ((addr as u128) << 2) & !(size - 1);
+                Some((start as u64, start + size))
+            }
+        }
+    }
+
+    // whether an access of `size` bytes needing `perm` is allowed
+    pub fn check(&self, address: u64, size: u64, privilege: Privilege, perm: u8) -> bool {
+        let last = address as u128 + size as u128;
+        for entry in 0..PMP_ENTRIES {
+            let (start, end) = match self.range(entry) {
+                Some(range) => range,
+                None => continue,
+            };
+            // the first entry that covers any of the bytes decides
+            if (address as u128) >= end || last <= start as u128 {
+                continue;
+            }
+            if address < start || last > end {
+                return false;
+            }
+            let cfg = self.cfg[entry];
+            return (privilege == Privilege::Machine && cfg & PMP_L == 0) || cfg & perm == perm;
+        }
+        privilege == Privilege::Machine
+    }
+}
+
+#[test]
+fn test_pmp() {
+    use Privilege::*;
+
+    let mut pmp = Pmp::default();
+    // with no entry, only M-mode may access memory
+    assert!(pmp.check(0x1000, 4, Machine, PMP_R));
+    assert!(!pmp.check(0x1000, 4, Supervisor, PMP_R));
+
+    // 0: NAPOT 4 KiB at 0x8000_0000, read-only and locked
+    // 1: TOR 0x8000_1000..0x8000_2000, RWX
+    // 2: NA4 at 0x10, execute-only
+    pmp.write_addr(0, (0x8000_0000 >> 2) | 0x1ff);
+    pmp.write_addr(1, 0x8000_2000 >> 2);
+    pmp.write_addr(2, 0x10 >> 2);
+    pmp.write_cfg(0, 0x14_0f_99);
+    assert_eq!(pmp.read_cfg(0), 0x14_0f_99);
+
+    assert!(pmp.check(0x8000_0ff8, 8, User, PMP_R));
+    assert!(!pmp.check(0x8000_0ff8, 8, User, PMP_W));
+    // locked, so M-mode is checked as well
+    assert!(!pmp.check(0x8000_0000, 8, Machine, PMP_W));
+    assert!(pmp.check(0x8000_1000, 8, User, PMP_R | PMP_W | PMP_X));
+    // straddles two entries
+    assert!(!pmp.check(0x8000_0ffc, 8, Supervisor, PMP_R));
+    assert!(pmp.check(0x10, 4, User, PMP_X));
+    assert!(!pmp.check(0x10, 4, User, PMP_R));
+    assert!(!pmp.check(0x8000_2000, 8, User, PMP_R));
+
+    // entry 0 is locked
+    pmp.write_cfg(0, 0);
+    pmp.write_addr(0, 0);
+    assert_eq!(pmp.read_cfg(0) & 0xff, 0x99);
+    assert_eq!(pmp.read_addr(0), (0x8000_0000 >> 2) | 0x1ff);
+    // write-only is not a valid combination
+    pmp.write_cfg(0, 0x0a00);
+    assert_eq!((pmp.read_cfg(0) >> 8) & 0xff, 0x08);
+}
@@ -0,0 +1,592 @@
+/// Guesses the direction of conditional branches at fetch, their targets
+/// come from the BTB.
+pub trait BranchPredictor {
+    // whether the branch at `pc` to `target` will be taken
+    fn predict(&self, pc: u64, target: u64) -> bool;
+
+    // learns how the branch at `pc` went
+    fn update(&mut self, pc: u64, taken: bool);
+
+    // the state it keeps, for comparing predictors of the same size
+    fn storage_bits(&self) -> u64 {
+        0
+    }
+}
+
+pub enum Static {
+    Taken,
+    NotTaken,
+    // backward taken, forward not taken, which suits loops
+    Btfn,
+}
+
+impl BranchPredictor for Static {
+    fn predict(&self, pc: u64, target: u64) -> bool {
+        match self {
+            Static::Taken => true,
+            Static::NotTaken => false,
+            Static::Btfn => target < pc,
+        }
+    }
+
+    fn update(&mut self, _pc: u64, _taken: bool) {}
+}
+
+// saturating counters, the upper half of each predicts taken
+struct Counters {
+    bits: u32,
+    table: Vec<u8>,
+}
+
+impl Counters {
+    // 2^`index_bits` counters, all weakly taken
+    fn new(index_bits: u32, bits: u32) -> Self {
+        Self {
+            bits,
+            table: vec![1 << (bits - 1); 1 << index_bits],
+        }
+    }
+
+    fn index(&self, x: u64) -> usize {
+        x as usize & (self.table.len() - 1)
+    }
+
+    fn taken(&self, x: u64) -> bool {
+        self.table[self.index(x)] >= 1 << (self.bits - 1)
+    }
+
+    fn storage_bits(&self) -> u64 {
+        self.table.len() as u64 * self.bits as u64
+    }
+
+    fn update(&mut self, x: u64, taken: bool) {
+        let i = self.index(x);
+        let max = (1 << self.bits) - 1;
+        self.table[i] = match taken {
+            true => (self.table[i] + 1).min(max),
+            false => self.table[i].saturating_sub(1),
+        };
+    }
+}
+
+// with compressed instructions, branches can sit at any even address
+fn key(pc: u64) -> u64 {
+    pc >> 1
+}
+
+// a counter per branch, one bit remembers the last outcome and two bits need
+// to be wrong twice before changing their mind
+pub struct Bimodal {
+    counters: Counters,
+}
+
+impl Bimodal {
+    pub fn new(index_bits: u32, bits: u32) -> Self {
+        Self { counters: Counters::new(index_bits, bits) }
+    }
+}
+
+impl BranchPredictor for Bimodal {
+    fn predict(&self, pc: u64, _target: u64) -> bool {
+        self.counters.taken(key(pc))
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        self.counters.update(key(pc), taken);
+    }
+
+    fn storage_bits(&self) -> u64 {
+        self.counters.storage_bits()
+    }
+}
+
+// 2-bit counters indexed by the pc xor the outcomes of the latest branches
+pub struct Gshare {
+    history: u64,
+    history_bits: u32,
+    counters: Counters,
+}
+
+impl Gshare {
+    pub fn new(index_bits: u32) -> Self {
+        Self {
+            history: 0,
+            history_bits: index_bits,
+            counters: Counters::new(index_bits, 2),
+        }
+    }
+
+    fn index(&self, pc: u64) -> u64 {
+        key(pc) ^ self.history
+    }
+}
+
+impl BranchPredictor for Gshare {
+    fn predict(&self, pc: u64, _target: u64) -> bool {
+        self.counters.taken(self.index(pc))
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        self.counters.update(self.index(pc), taken);
+        self.history = ((self.history << 1) | taken as u64) & ((1 << self.history_bits) - 1);
+    }
+
+    fn storage_bits(&self) -> u64 {
+        self.counters.storage_bits() + self.history_bits as u64
+    }
+}
+
+// each branch keeps its own history, which picks a 2-bit counter shared by
+// all branches
+pub struct Local {
+    histories: Vec<u64>,
+    history_bits: u32,
+    counters: Counters,
+}
+
+impl Local {
+    pub fn new(index_bits: u32) -> Self {
+        Self {
+            histories: vec![0; 1 << index_bits],
+            history_bits: index_bits,
+            counters: Counters::new(index_bits, 2),
+        }
+    }
+
+    fn history(&self, pc: u64) -> u64 {
+        self.histories[key(pc) as usize & (self.histories.len() - 1)]
+    }
+}
+
+impl BranchPredictor for Local {
+    fn predict(&self, pc: u64, _target: u64) -> bool {
+        self.counters.taken(self.history(pc))
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        let history = self.history(pc);
+        self.counters.update(history, taken);
+        let i = key(pc) as usize & (self.histories.len() - 1);
+        self.histories[i] = ((history << 1) | taken as u64) & ((1 << self.history_bits) - 1);
+    }
+
+    fn storage_bits(&self) -> u64 {
+        self.histories.len() as u64 * self.history_bits as u64 + self.counters.storage_bits()
+    }
+}
+
+// gshare and local side by side, with a 2-bit counter per branch choosing
+// whichever of them has been right more often
+pub struct Tournament {
+    global: Gshare,
+    local: Local,
+    // taken picks the global one
+    chooser: Counters,
+}
+
+impl Tournament {
+    pub fn new(index_bits: u32) -> Self {
+        Self {
+            global: Gshare::new(index_bits),
+            local: Local::new(index_bits),
+            chooser: Counters::new(index_bits, 2),
+        }
+    }
+}
+
+impl BranchPredictor for Tournament {
+    fn predict(&self, pc: u64, target: u64) -> bool {
+        match self.chooser.taken(key(pc)) {
+            true => self.global.predict(pc, target),
+            false => self.local.predict(pc, target),
+        }
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        let global = self.global.predict(pc, 0);
+        if global != self.local.predict(pc, 0) {
+            self.chooser.update(key(pc), global == taken);
+        }
+        self.global.update(pc, taken);
+        self.local.update(pc, taken);
+    }
+
+    fn storage_bits(&self) -> u64 {
+        self.global.storage_bits() + self.local.storage_bits() + self.chooser.storage_bits()
+    }
+}
+
+// the outcomes of the latest branches, as long as the longest history used
+struct History {
+    bits: Vec<bool>,
+    head: usize,
+}
+
+impl History {
+    fn new(length: usize) -> Self {
+        Self {
+            bits: vec![false; length + 1],
+            head: 0,
+        }
+    }
+
+    // 0 is the latest
+    fn get(&self, i: usize) -> bool {
+        self.bits[(self.head + i) % self.bits.len()]
+    }
+
+    fn push(&mut self, taken: bool) {
+        self.head = (self.head + self.bits.len() - 1) % self.bits.len();
+        self.bits[self.head] = taken;
+    }
+}
+
+// the latest `length` outcomes xor-folded into `width` bits, updated one
+// outcome at a time instead of refolding the whole history
+#[derive(Clone, Copy)]
+struct Folded {
+    length: usize,
+    width: u32,
+    value: u64,
+}
+
+impl Folded {
+    fn new(length: usize, width: u32) -> Self {
+        Self { length, width, value: 0 }
+    }
+
+    // after `history.push`
+    fn update(&mut self, history: &History) {
+        self.value = (self.value << 1) | history.get(0) as u64;
+        self.value ^= (history.get(self.length) as u64) << (self.length as u32 % self.width);
+        self.value ^= self.value >> self.width;
+        self.value &= (1 << self.width) - 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TageConfig {
+    // log2 of the entries of the base predictor and of each tagged table
+    pub index_bits: u32,
+    // of each tagged table, shortest first
+    pub history_lengths: Vec<usize>,
+    pub tag_bits: u32,
+    // branches between two resets of all useful counters
+    pub reset_period: u64,
+}
+
+impl TageConfig {
+    // `tables` history lengths growing geometrically from `min` to `max`
+    pub fn geometric(index_bits: u32, tables: usize, min: usize, max: usize, tag_bits: u32,
+                     reset_period: u64) -> Self {
+        let ratio = (max as f64 / min as f64).powf(1.0 / (tables.max(2) - 1) as f64);
+        let history_lengths = (0..tables)
+            .map(|i| (min as f64 * ratio.powi(i as i32)).round() as usize)
+            .collect();
+        Self { index_bits, history_lengths, tag_bits, reset_period }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct TageEntry {
+    tag: u64,
+    // 3-bit signed, taken when not negative
+    counter: i8,
+    // 2 bits, whether it has been right where the next shorter one was not
+    useful: u8,
+}
+
+struct TaggedTable {
+    entries: Vec<TageEntry>,
+    index: Folded,
+    // folded twice so that tags differ from indexes
+    tag: [Folded; 2],
+}
+
+/// TAGE, after Seznec and Michaud: a bimodal base predictor, and tables
+/// tagged with the pc and ever longer global histories where the longest
+/// match predicts. Mispredictions allocate in a longer table.
+pub struct Tage {
+    config: TageConfig,
+    base: Counters,
+    tables: Vec<TaggedTable>,
+    history: History,
+    branches: u64,
+}
+
+impl Tage {
+    pub fn new(config: TageConfig) -> Self {
+        let tables = config.history_lengths.iter().map(|&length| TaggedTable {
+            entries: vec![TageEntry::default(); 1 << config.index_bits],
+            index: Folded::new(length, config.index_bits),
+            tag: [Folded::new(length, config.tag_bits), Folded::new(length, config.tag_bits - 1)],
+        }).collect();
+        let longest = config.history_lengths.iter().copied().max().unwrap_or(0);
+        Self {
+            base: Counters::new(config.index_bits, 2),
+            tables,
+            history: History::new(longest),
+            branches: 0,
+            config,
+        }
+    }
+
+    // where the branch at `pc` sits in each table, and its tag there
+    fn slot(&self, table: &TaggedTable, pc: u64) -> (usize, u64) {
+        let (pc, bits) = (key(pc), self.config.index_bits);
+        let index = (pc ^ (pc >> bits) ^ table.index.value) & ((1 << bits) - 1);
+        let tag = (pc ^ table.tag[0].value ^ (table.tag[1].value << 1))
+            & ((1 << self.config.tag_bits) - 1);
+        (index as usize, tag)
+    }
+
+    // the tables whose entry matches, longest history first
+    fn hits(&self, pc: u64) -> Vec<(usize, usize)> {
+        self.tables.iter().enumerate().rev().filter_map(|(t, table)| {
+            let (index, tag) = self.slot(table, pc);
+            match table.entries[index].tag == tag {
+                true => Some((t, index)),
+                false => None,
+            }
+        }).collect()
+    }
+
+    fn taken(&self, hit: Option<&(usize, usize)>, pc: u64) -> bool {
+        match hit {
+            Some(&(t, i)) => self.tables[t].entries[i].counter >= 0,
+            None => self.base.taken(key(pc)),
+        }
+    }
+}
+
+impl BranchPredictor for Tage {
+    fn predict(&self, pc: u64, _target: u64) -> bool {
+        self.taken(self.hits(pc).first(), pc)
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        let hits = self.hits(pc);
+        let prediction = self.taken(hits.first(), pc);
+        let alternate = self.taken(hits.get(1), pc);
+        match hits.first() {
+            Some(&(t, i)) => {
+                let entry = &mut self.tables[t].entries[i];
+                if prediction != alternate {
+                    entry.useful = match prediction == taken {
+                        true => (entry.useful + 1).min(3),
+                        false => entry.useful.saturating_sub(1),
+                    };
+                }
+                entry.counter = match taken {
+                    true => (entry.counter + 1).min(3),
+                    false => (entry.counter - 1).max(-4),
+                };
+            }
+            None => self.base.update(key(pc), taken),
+        }
+
+        // a longer history might have got it right, take an entry nobody
+        // finds useful, or make some room for next time
+        let longer = hits.first().map_or(0, |x| x.0 + 1);
+        if prediction != taken && longer < self.tables.len() {
+            let slots: Vec<(usize, u64)> = self.tables[longer..].iter()
+                .map(|table| self.slot(table, pc))
+                .collect();
+            let free = slots.iter().zip(&self.tables[longer..])
+                .position(|(&(i, _), table)| table.entries[i].useful == 0);
+            match free {
+                Some(n) => {
+                    let (i, tag) = slots[n];
+                    self.tables[longer + n].entries[i] = TageEntry {
+                        tag,
+                        counter: if taken { 0 } else { -1 },
+                        useful: 0,
+                    };
+                }
+                None => {
+                    for (&(i, _), table) in slots.iter().zip(&mut self.tables[longer..]) {
+                        table.entries[i].useful = table.entries[i].useful.saturating_sub(1);
+                    }
+                }
+            }
+        }
+
+        self.branches += 1;
+        if self.branches.is_multiple_of(self.config.reset_period) {
+            for entry in self.tables.iter_mut().flat_map(|x| x.entries.iter_mut()) {
+                entry.useful = 0;
+            }
+        }
+        self.history.push(taken);
+        for table in &mut self.tables {
+            table.index.update(&self.history);
+            table.tag[0].update(&self.history);
+            table.tag[1].update(&self.history);
+        }
+    }
+
+    fn storage_bits(&self) -> u64 {
+        let entry = self.config.tag_bits as u64 + 3 + 2;
+        let tagged = self.tables.len() as u64 * (entry << self.config.index_bits);
+        self.base.storage_bits() + tagged + self.history.bits.len() as u64 - 1
+    }
+}
+
+// `x` xor-folded into `bits` bits
+fn fold(mut x: u64, bits: u32) -> u64 {
+    let mut folded = 0;
+    while x != 0 {
+        folded ^= x & ((1 << bits) - 1);
+        x >>= bits;
+    }
+    folded
+}
+
+/// A hashed perceptron, after Tarjan and Skadron: each table is indexed by
+/// the pc hashed with its own slice of the global history, and the branch
+/// is predicted taken when the sum of the weights read is not negative.
+pub struct Perceptron {
+    index_bits: u32,
+    // the first table is indexed by the pc alone, as a bias
+    weights: Vec<Vec<i8>>,
+    history: u64,
+    history_bits: u32,
+    // trains until the sum is further than this from zero
+    threshold: i32,
+}
+
+// the low `bits` bits, up to all 64 of them
+fn mask(bits: u32) -> u64 {
+    match bits {
+        64 => u64::MAX,
+        n => (1 << n) - 1,
+    }
+}
+
+impl Perceptron {
+    // `tables` slices of a `history_bits` long history, at most 64
+    pub fn new(index_bits: u32, tables: u32, history_bits: u32) -> Self {
+        let weights = tables as usize + 1;
+        Self {
+            index_bits,
+            weights: vec![vec![0; 1 << index_bits]; weights],
+            history: 0,
+            history_bits,
+            threshold: (1.93 * weights as f64 + 14.0) as i32,
+        }
+    }
+
+    fn indexes(&self, pc: u64) -> Vec<usize> {
+        let slices = (self.weights.len() - 1) as u32;
+        let width = self.history_bits.div_ceil(slices);
+        (0..=slices).map(|i| {
+            // with more tables than history bits, the last slices are empty
+            let shift = i.saturating_sub(1) * width;
+            let slice = match i {
+                0 => 0,
+                _ if shift >= self.history_bits => 0,
+                _ => (self.history >> shift) & mask(width),
+            };
+            ((key(pc) ^ fold(slice, self.index_bits)) & ((1 << self.index_bits) - 1)) as usize
+        }).collect()
+    }
+
+    fn sum(&self, pc: u64) -> i32 {
+        self.indexes(pc).iter().zip(&self.weights).map(|(&i, x)| x[i] as i32).sum()
+    }
+}
+
+impl BranchPredictor for Perceptron {
+    fn predict(&self, pc: u64, _target: u64) -> bool {
+        self.sum(pc) >= 0
+    }
+
+    fn update(&mut self, pc: u64, taken: bool) {
+        let sum = self.sum(pc);
+        if (sum >= 0) != taken || sum.abs() <= self.threshold {
+            for (i, weights) in self.indexes(pc).into_iter().zip(&mut self.weights) {
+                weights[i] = match taken {
+                    true => weights[i].saturating_add(1),
+                    false => weights[i].saturating_sub(1),
+                };
+            }
+        }
+        self.history = ((self.history << 1) | taken as u64) & mask(self.history_bits);
+    }
+
+    fn storage_bits(&self) -> u64 {
+        8 * ((self.weights.len() as u64) << self.index_bits) + self.history_bits as u64
+    }
+}
+
+// `index_bits` sizes the tables of the dynamic predictors
+pub fn new_predictor(name: &str, index_bits: u32) -> Option<Box<dyn BranchPredictor>> {
+    Some(match name {
+        "taken" => Box::new(Static::Taken),
+        "not-taken" => Box::new(Static::NotTaken),
+        "btfn" => Box::new(Static::Btfn),
+        "1bit" => Box::new(Bimodal::new(index_bits, 1)),
+        "2bit" => Box::new(Bimodal::new(index_bits, 2)),
+        "gshare" => Box::new(Gshare::new(index_bits)),
+        "local" => Box::new(Local::new(index_bits)),
+        "tournament" => Box::new(Tournament::new(index_bits)),
+        "tage" => Box::new(Tage::new(TageConfig::geometric(index_bits, 7, 4, 256, 9, 1 << 18))),
+        "perceptron" => Box::new(Perceptron::new(index_bits, 8, 64)),
+        _ => return None,
+    })
+}
+
+#[test]
+fn test_predictors() {
+    // a loop branch taken three times and then falling through
+    let mispredictions = |name| {
+        let mut predictor = new_predictor(name, 10).unwrap();
+        let (pc, target) = (0x1010, 0x1000);
+        let mut wrong = 0;
+        for i in 0..400 {
+            let taken = i % 4 != 3;
+            if predictor.predict(pc, target) != taken && i >= 200 {
+                wrong += 1;
+            }
+            predictor.update(pc, taken);
+        }
+        wrong
+    };
+    assert_eq!(mispredictions("taken"), 50);
+    assert_eq!(mispredictions("not-taken"), 150);
+    assert_eq!(mispredictions("btfn"), 50);
+    assert_eq!(mispredictions("1bit"), 100);
+    assert_eq!(mispredictions("2bit"), 50);
+    assert_eq!(mispredictions("gshare"), 0);
+    assert_eq!(mispredictions("local"), 0);
+    assert_eq!(mispredictions("tournament"), 0);
+    assert_eq!(mispredictions("tage"), 0);
+    assert_eq!(mispredictions("perceptron"), 0);
+    assert!(new_predictor("oracle", 10).is_none());
+
+    // a loop exiting every 24 iterations, which 10 bits of history miss
+    let mispredictions = |predictor: &mut dyn BranchPredictor| {
+        let mut wrong = 0;
+        for i in 0..24 * 300 {
+            let taken = i % 24 != 23;
+            if predictor.predict(0x1000, 0x800) != taken && i >= 24 * 250 {
+                wrong += 1;
+            }
+            predictor.update(0x1000, taken);
+        }
+        wrong
+    };
+    let mut gshare = Gshare::new(10);
+    let mut tage = Tage::new(TageConfig::geometric(10, 4, 4, 64, 9, 1 << 18));
+    let mut perceptron = Perceptron::new(10, 8, 64);
+    assert!(mispredictions(&mut gshare) > 0);
+    assert_eq!(mispredictions(&mut tage), 0);
+    assert_eq!(mispredictions(&mut perceptron), 0);
+    // a single 64 bit slice, and more tables than slices of history
+    mispredictions(&mut Perceptron::new(12, 1, 64));
+    mispredictions(&mut Perceptron::new(12, 33, 64));
+
+    assert_eq!(Bimodal::new(10, 2).storage_bits(), 2048);
+    assert_eq!(tage.storage_bits(), 2048 + 4 * 1024 * 14 + 64);
+    assert_eq!(perceptron.storage_bits(), 9 * 1024 * 8 + 64);
+}
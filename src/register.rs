@@ -2,13 +2,23 @@ use std::fmt::{Debug, Formatter, Error};
 
 const REG_NUM: usize = 32;
 
-const REG_NAME: &'static [&'static str; REG_NUM] = &[
+const REG_NAME: &[&str; REG_NUM] = &[
     "zero", "ra", "sp",  "gp",  "tp", "t0", "t1", "t2",
     "s0",   "s1", "a0",  "a1",  "a2", "a3", "a4", "a5",
     "a6",   "a7", "s2",  "s3",  "s4", "s5", "s6", "s7",
     "s8",   "s9", "s10", "s11", "t3", "t4", "t5", "t6"
 ];
 
+const FREG_NAME: &[&str; REG_NUM] = &[
+    "ft0", "ft1", "ft2",  "ft3",  "ft4", "ft5", "ft6",  "ft7",
+    "fs0", "fs1", "fa0",  "fa1",  "fa2", "fa3", "fa4",  "fa5",
+    "fa6", "fa7", "fs2",  "fs3",  "fs4", "fs5", "fs6",  "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11"
+];
+
+// upper half of a single-precision value held in a 64-bit register
+const NAN_BOX: u64 = 0xffff_ffff_0000_0000;
+
 #[derive(Copy, Clone, Default)]
 pub struct Reg {
     index: u8,
@@ -16,7 +26,10 @@ pub struct Reg {
 
 impl Debug for Reg {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}", REG_NAME[self.index as usize])
+        match self.index as usize {
+            i if i < REG_NUM => write!(f, "{}", REG_NAME[i]),
+            i => write!(f, "{}", FREG_NAME[i - REG_NUM]),
+        }
     }
 }
 
@@ -24,6 +37,16 @@ impl Reg {
     pub fn not_zero(&self) -> bool {
         self.index != 0
     }
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    // the same number in the FP register file, kept apart from the integer
+    // one where both end up side by side, as in `ExecuteInfo`
+    pub fn fp(self) -> Reg {
+        Reg { index: self.index + REG_NUM as u8 }
+    }
 }
 
 impl std::cmp::PartialEq for Reg {
@@ -78,9 +101,8 @@ impl RegisterFile {
     }
 
     pub fn println(&self) {
-        for i in 1..REG_NUM {
+        for (i, name) in REG_NAME.iter().enumerate().skip(1) {
             let reg = Reg { index: i as u8 };
-            let name = REG_NAME[i];
             let value = self.get(reg);
             print!("{:<3}={:0>16x} ", name, value);
             if i % 4 == 0 {
@@ -89,4 +111,56 @@ impl RegisterFile {
         }
         println!()
     }
-}
\ No newline at end of file
+}
+
+/// f0-f31. Registers hold raw bits; single-precision values are NaN-boxed.
+pub struct FRegisterFile {
+    regs: [u64; REG_NUM],
+}
+
+impl FRegisterFile {
+    pub fn new() -> Self {
+        Self {
+            regs: [0; REG_NUM]
+        }
+    }
+
+    pub fn get(&self, reg: Reg) -> u64 {
+        self.regs[reg.index as usize]
+    }
+
+    pub fn set(&mut self, reg: Reg, value: u64) {
+        self.regs[reg.index as usize] = value
+    }
+
+    // a value that is not properly NaN-boxed reads as the canonical NaN
+    pub fn get_s(&self, reg: Reg) -> f32 {
+        let value = self.get(reg);
+        if value & NAN_BOX == NAN_BOX {
+            f32::from_bits(value as u32)
+        } else {
+            f32::from_bits(0x7fc0_0000)
+        }
+    }
+
+    pub fn set_s(&mut self, reg: Reg, value: f32) {
+        self.set(reg, NAN_BOX | value.to_bits() as u64)
+    }
+
+    pub fn get_d(&self, reg: Reg) -> f64 {
+        f64::from_bits(self.get(reg))
+    }
+
+    pub fn set_d(&mut self, reg: Reg, value: f64) {
+        self.set(reg, value.to_bits())
+    }
+
+    pub fn println(&self) {
+        for (i, name) in FREG_NAME.iter().enumerate() {
+            print!("{:<4}={:0>16x} ", name, self.regs[i]);
+            if i % 4 == 3 {
+                println!()
+            }
+        }
+    }
+}
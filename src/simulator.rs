@@ -1,28 +1,113 @@
 use std::cmp::max;
 use std::fs::File;
 use std::io::{Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
 
-use objdump::Elf;
+use objdump::{Elf, PT_LOAD};
 
 use crate::action;
 use crate::action::{ExecuteInfo, Instruction};
+use crate::btb::{self, TargetPredictor};
 use crate::cache;
 use crate::cache::{Storage};
-use crate::memory::{Memory, MemorySegment};
-use crate::register::{RegisterFile, from_name};
+use crate::compressed;
+use crate::csr::CsrFile;
+use crate::htif::Htif;
+use crate::memory::{Memory, Misaligned, PERM_RW, PERM_X};
+use crate::mmu::Mmu;
+use crate::pipeline::Pipeline;
+use crate::predictor::{self, BranchPredictor};
+use crate::tlb::{self, Tlb};
+use crate::register::{RegisterFile, FRegisterFile};
 use crate::statistic::Statistic;
+use crate::syscall::ProxyKernel;
+use crate::trap::Exception;
 
-const STACK_ADDRESS: u64 = 0x3f3f3f_fffff;
-const STACK_SIZE: usize = 4096;
+pub const DEFAULT_STACK_TOP: u64 = 0x3f_ffff_f000;
+pub const DEFAULT_STACK_SIZE: u64 = 8 << 20;
+pub const DEFAULT_HEAP_SIZE: u64 = 256 << 20;
+// where QEMU `virt` has its DRAM, and how much it has by default
+pub const VIRT_RAM_BASE: u64 = 0x8000_0000;
+pub const VIRT_RAM_SIZE: u64 = 128 << 20;
+// left unmapped below the stack, faults there are stack overflows
+const STACK_GUARD: u64 = 1 << 20;
+
+// auxiliary vector entries, see <linux/auxvec.h>
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_HWCAP: u64 = 16;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StartMode {
+    // run the C runtime from the ELF entry point
+    Entry,
+    // call `main` directly; returning from it ends the simulation
+    Main,
+}
+
+pub struct LoadConfig {
+    pub start: StartMode,
+    // argv[0] included
+    pub args: Vec<String>,
+    pub env: Vec<String>,
+    // the stack grows down from `stack_top` by at most `stack_size` bytes
+    pub stack_top: u64,
+    pub stack_size: u64,
+    // how far brk may move the program break
+    pub heap_size: u64,
+    // RAM mapped RWX at `base` for `size` bytes, besides the ELF segments
+    pub ram: Option<(u64, u64)>,
+}
+
+impl LoadConfig {
+    pub fn new(filename: &str) -> Self {
+        Self {
+            start: StartMode::Entry,
+            args: vec![filename.to_string()],
+            env: Vec::new(),
+            stack_top: DEFAULT_STACK_TOP,
+            stack_size: DEFAULT_STACK_SIZE,
+            heap_size: DEFAULT_HEAP_SIZE,
+            ram: None,
+        }
+    }
+}
 
 pub struct Simulator {
     pub memory: Memory,
     pub regs: RegisterFile,
+    pub fregs: FRegisterFile,
     pub elf: Elf,
     pub pc: u64,
     pub stat: Statistic,
     pub cache: Box<dyn Storage>,
-    pub instr: [ExecuteInfo; 5],
+    pub itlb: Tlb,
+    pub dtlb: Tlb,
+    pub pipeline: Pipeline,
+    pub predictor: Box<dyn BranchPredictor>,
+    pub targets: TargetPredictor,
+    pub kernel: ProxyKernel,
+    pub csr: CsrFile,
+    pub fault: Option<Exception>,
+    // address reserved by the last LR
+    pub reservation: Option<u64>,
+    pub stack_guard: Range<u64>,
+    // set when the program has a `tohost` symbol
+    pub htif: Option<Htif>,
+    // print loaded segments and every instruction run
+    pub trace: bool,
 }
 
 impl Simulator {
@@ -30,90 +115,699 @@ impl Simulator {
         Simulator {
             memory: Memory::new(),
             regs: RegisterFile::new(),
+            fregs: FRegisterFile::new(),
             pc: 0,
             elf: Elf::default(),
             stat: Statistic::default(),
             cache: cache::new_3_levels(),
-            instr: [ExecuteInfo::default(); 5],
+            itlb: tlb::default_itlb(),
+            dtlb: tlb::default_dtlb(),
+            pipeline: Pipeline::new(),
+            predictor: Box::new(predictor::Static::Taken),
+            targets: btb::default_targets(),
+            kernel: ProxyKernel::new(PathBuf::from(".")),
+            csr: CsrFile::new(),
+            fault: None,
+            reservation: None,
+            stack_guard: 0..0,
+            htif: None,
+            trace: true,
         }
     }
 
-    pub fn load_from_elf(&mut self, filename: &str) {
+    // fails when the program does not fit with the stack laid out by `config`
+    pub fn load_from_elf(&mut self, filename: &str, config: &LoadConfig) -> Result<(), String> {
         let elf: Elf = Elf::open(filename)
             .expect("can not open the binary file");
 
+        // kernels hand out pages from it and may run code they copied there
+        if let Some((base, size)) = config.ram {
+            self.memory.alloc(base, size as usize, PERM_RW | PERM_X);
+        }
+
         let mut f = File::open(filename).unwrap();
-        elf.programs.iter().for_each(|segment| {
+        let mut highest = 0;
+        elf.programs.iter().filter(|p| p.ptype == PT_LOAD).for_each(|segment| {
             let _ = f.seek(SeekFrom::Start(segment.off)).unwrap();
             debug_assert!(segment.memsz >= segment.filesz);
-            let mut seg = MemorySegment::new(
-                segment.vaddr, segment.memsz as usize);
-            println!("load segment {:x} ~ {:x}",
-                     segment.vaddr,
-                     segment.vaddr + segment.memsz);
-            seg.load_from(&mut f, segment.filesz as usize);
-            self.memory.push(seg);
+            if self.trace {
+                println!("load segment {:x} ~ {:x}",
+                         segment.vaddr,
+                         segment.vaddr + segment.memsz);
+            }
+            self.memory.alloc(segment.vaddr, segment.memsz as usize, segment.flags as u8);
+            // .bss only segments have nothing in the file
+            if segment.filesz > 0 {
+                self.memory.load_from(segment.vaddr, &mut f, segment.filesz as usize);
+            }
+            highest = max(highest, segment.vaddr + segment.memsz);
         });
 
-        self.memory.push(MemorySegment::new(
-            STACK_ADDRESS - STACK_SIZE as u64, STACK_SIZE));
-        self.regs.set(from_name("sp"), STACK_ADDRESS);
+        let top = config.stack_top & !0xf;
+        let bottom = top.saturating_sub(config.stack_size) & !0xfff;
+        self.stack_guard = bottom.saturating_sub(STACK_GUARD)..bottom;
+        if self.stack_guard.start <= highest {
+            return Err(format!("the stack at {:x} overlaps the program ending at {:x}",
+                               self.stack_guard.start, highest));
+        }
+        self.memory.alloc(bottom, (top - bottom) as usize, PERM_RW);
+        let heap_limit = highest.saturating_add(config.heap_size)
+            .min(self.stack_guard.start);
+        self.kernel.init_brk(highest, heap_limit);
+
+        let (sp, argv, envp) = self.push_arguments(&elf, config)
+            .map_err(|_| "the arguments do not fit on the stack".to_string())?;
+        self.regs.set_by_name("sp", sp);
 
-        let main = elf.symbol_entries.iter()
-            .filter(|x| {
-                x.0.contains("main")
-            }).next().unwrap();
-        self.pc = main.1;
+        let symbol = |name: &str| elf.symbol_entries.iter()
+            .find(|x| x.0 == name)
+            .map(|x| x.1);
+        if let Some(tohost) = symbol("tohost") {
+            self.memory.watch(tohost);
+            self.htif = Some(Htif { tohost, fromhost: symbol("fromhost") });
+        }
+        match config.start {
+            StartMode::Entry => self.pc = elf.header.entry,
+            StartMode::Main => {
+                self.pc = symbol("main").expect("can not find main");
+                // normally set up by crt0
+                if let Some(gp) = symbol("__global_pointer$") {
+                    self.regs.set_by_name("gp", gp);
+                }
+                self.regs.set_by_name("a0", config.args.len() as u64);
+                self.regs.set_by_name("a1", argv);
+                self.regs.set_by_name("a2", envp);
+            }
+        }
         self.elf = elf;
+        Ok(())
     }
 
-    fn decode(&mut self) -> Instruction {
-        let inst: u32 = self.memory.load_u32(self.pc);
-        if (inst & 0b11) != 0b11 {
-            panic!("oa, it's a 16bit instruction");
+    // lays out argc, argv, envp and auxv like Linux does for a new process,
+    // returns the new sp and the addresses of argv and envp
+    fn push_arguments(&mut self, elf: &Elf, config: &LoadConfig)
+                      -> Result<(u64, u64, u64), Exception> {
+        let mut top = config.stack_top & !0xf;
+        let mut push_bytes = |memory: &mut Memory, bytes: &[u8]| {
+            top -= bytes.len() as u64;
+            for (i, b) in bytes.iter().enumerate() {
+                memory.store_u8(top + i as u64, *b)?;
+            }
+            Ok(top)
+        };
+        // fixed, so that runs are reproducible
+        let random = push_bytes(&mut self.memory, b"riscv-sim random")?;
+        let mut strings = |list: &[String]| -> Result<Vec<u64>, Exception> {
+            list.iter()
+                .map(|s| push_bytes(&mut self.memory, format!("{}\0", s).as_bytes()))
+                .collect()
+        };
+        let env = strings(&config.env)?;
+        let args = strings(&config.args)?;
+
+        let header = &elf.header;
+        let phdr = elf.programs.iter()
+            .find(|p| p.ptype == PT_LOAD && p.off <= header.phoff && header.phoff < p.off + p.filesz)
+            .map_or(0, |p| p.vaddr + header.phoff - p.off);
+        let auxv = [
+            (AT_PHDR, phdr),
+            (AT_PHENT, 0x38),
+            (AT_PHNUM, header.phnum as u64),
+            (AT_PAGESZ, 4096),
+            (AT_ENTRY, header.entry),
+            (AT_UID, 0),
+            (AT_EUID, 0),
+            (AT_GID, 0),
+            (AT_EGID, 0),
+            (AT_HWCAP, self.csr.misa & 0x3ff_ffff),
+            (AT_CLKTCK, 100),
+            (AT_SECURE, 0),
+            (AT_RANDOM, random),
+            (AT_NULL, 0),
+        ];
+
+        let mut table = vec![args.len() as u64];
+        table.extend(&args);
+        table.push(0);
+        table.extend(&env);
+        table.push(0);
+        auxv.iter().for_each(|(key, value)| table.extend(&[*key, *value]));
+
+        let sp = (top - table.len() as u64 * 8) & !0xf;
+        for (i, word) in table.iter().enumerate() {
+            self.memory.store_u64(sp + i as u64 * 8, *word)?;
         }
-        if (inst & 0b11100) == 0b11100 {
-            panic!("it's an instruction that is longer that 32bit");
+        let argv = sp + 8;
+        Ok((sp, argv, argv + (args.len() as u64 + 1) * 8))
+    }
+
+    // returns the instruction, its raw bits, its length and the cycles its
+    // fetch stalled for
+    fn decode(&mut self) -> Result<(Instruction, u32, u64, u64), Exception> {
+        let mut mmu = Mmu::new(&mut self.memory, self.csr.translation());
+        let low = mmu.fetch_u16(self.pc)?;
+        let (code, inst, len) = if low & 0b11 != 0b11 {
+            let inst = compressed::expand(low)
+                .ok_or(Exception::IllegalInstruction(low as u64))?;
+            (low as u32, inst, 2)
+        } else {
+            // fetched in halves, as it may straddle two pages
+            let high = mmu.fetch_u16(self.pc + 2)?;
+            let inst = ((high as u32) << 16) | low as u32;
+            // longer-than-32-bit encodings are not supported
+            if (inst & 0b11100) == 0b11100 {
+                return Err(Exception::IllegalInstruction(inst as u64));
+            }
+            (inst, inst, 4)
+        };
+        // fetch is not timed, apart from ITLB misses. Both halves are one
+        // lookup, unless they are on different pages
+        mmu.walks.dedup_by_key(|x| x.page);
+        let mut stall = 0;
+        for walk in &mmu.walks {
+            stall += self.itlb.access(walk, &mut self.cache);
         }
 
-        action::matching(inst)
+        match action::matching(inst) {
+            Some(i) => Ok((i, code, len, stall)),
+            None => Err(Exception::IllegalInstruction(code as u64)),
+        }
     }
 
     pub fn run(&mut self) -> bool {
-        if self.pc == 0 {
+        if self.pc == 0 || self.kernel.exited() || self.fault.is_some() {
+            self.pipeline.drain(&mut self.stat);
             return false
         }
-        print!("{:<7x}", self.pc);
-        let inst = self.decode();
-        println!("{:?}", inst);
-        self.single_step(inst);
+        if let Some(code) = self.csr.interrupt() {
+            self.pc = self.csr.interrupt_enter(self.pc, code);
+            self.pipeline.redirect();
+            self.stat.num_interrupt += 1;
+            return true;
+        }
+        let result = self.decode().and_then(|(inst, code, len, stall)| {
+            if self.trace {
+                println!("{:<7x}{:?}", self.pc, inst);
+            }
+            self.single_step(inst, len, stall).map_err(|e| match e {
+                // the CSR file does not know the encoding that failed
+                Exception::IllegalInstruction(_) =>
+                    Exception::IllegalInstruction(code as u64),
+                e => e,
+            })
+        });
+        if let Err(e) = result {
+            // it still goes down the pipeline, the handler is fetched after it
+            let info = ExecuteInfo::default();
+            self.pipeline.issue(self.pc, info, 0, true, &mut self.stat);
+            self.trap(e);
+        }
+        if let Some(htif) = &self.htif {
+            if self.memory.take_watch_hit() {
+                htif.poll(&mut self.memory, &mut self.kernel);
+            }
+        }
+        let bus = self.memory.bus_mut();
+        bus.tick(self.stat.cycle);
+        self.csr.lines = bus.interrupts.get();
+        self.csr.time_offset = bus.time_offset.get();
         true
     }
 
-    fn single_step(&mut self, inst: Instruction) {
-        self.stat.num_inst += 1;
-        self.instr[4] = self.instr[3];    // WB
-        self.instr[3] = self.instr[2];    // MEM
-        self.instr[2] = self.instr[1];    // EX
-        self.instr[1] = self.instr[0];    // ID
-        self.instr[0] = action::execute(self, inst);
-        let mut cycles = max(
-            self.instr[3].mem_access,
-            self.instr[2].exe_cycles);
-        let load_reg = self.instr[3].load_reg;
-        if load_reg.not_zero() {
-            if self.instr[2].reg_read[0] == load_reg
-                || self.instr[2].reg_read[1] == load_reg {
-                cycles = max(cycles, 2);
-                self.stat.num_data_hazard += 1;
+    // the function containing `address`, or the closest label before it
+    pub fn symbol_at(&self, address: u64) -> Option<(&str, u64)> {
+        let symbols = &self.elf.symbol_entries;
+        symbols.iter()
+            .find(|(_, start, size)| *start <= address && address < start + size)
+            .or_else(|| symbols.iter()
+                .filter(|(name, start, size)| {
+                    *size == 0 && *start <= address && name != "<null>"
+                })
+                .max_by_key(|(_, start, _)| *start))
+            .map(|(name, start, _)| (name.as_str(), address - start))
+    }
+
+    // what a fault the guest does not handle is reported as
+    fn fault_report(&self, e: Exception) -> String {
+        let what = match e {
+            Exception::LoadAccessFault(x) | Exception::StoreAccessFault(x)
+            if self.stack_guard.contains(&x) =>
+                format!("stack overflow (access to {:x})", x),
+            e => e.to_string(),
+        };
+        match self.symbol_at(self.pc) {
+            Some((name, offset)) => format!("{} at pc {:x} <{}+{:#x}>", what, self.pc, name, offset),
+            None => format!("{} at pc {:x}", what, self.pc),
+        }
+    }
+
+    fn trap(&mut self, e: Exception) {
+        // without a trap vector, ECALLs go to the proxy kernel
+        let handled = self.csr.trap_vector(e) != 0;
+        if !handled && e == Exception::EnvironmentCallFromMMode {
+            self.kernel.ecall(&mut self.regs, &mut self.memory);
+            self.pc += 4;
+            // serviced, so it retires like any other instruction
+            self.stat.num_inst += 1;
+            return;
+        }
+        if !handled {
+            eprintln!("{}", self.fault_report(e));
+            self.fault = Some(e);
+            return;
+        }
+        self.pc = self.csr.trap_enter(self.pc, e);
+    }
+
+    // WFI sleeps until the next timer event, unless an interrupt is already
+    // pending. Without one to wait for it does nothing.
+    fn wait(&mut self) {
+        if self.csr.pending() != 0 {
+            return;
+        }
+        if let Some(event) = self.memory.bus_mut().next_event() {
+            self.pipeline.skip_to(event, &mut self.stat);
+        }
+    }
+
+    // executes the instruction, then hands it to the pipeline for timing
+    fn single_step(&mut self, inst: Instruction, len: u64, fetch_stall: u64)
+                   -> Result<(), Exception> {
+        let (pc, misaligned) = (self.pc, self.memory.misaligned());
+        let sfence = matches!(inst, Instruction::SFENCEVMA(_));
+        let wfi = matches!(inst, Instruction::WFI(_));
+        let result = action::execute(self, inst, len);
+        let mut penalty = 0;
+        if self.memory.misaligned() != misaligned {
+            self.stat.record_misaligned(pc);
+            if let Misaligned::Penalty(cycles) = self.memory.policy {
+                penalty = cycles;
             }
         }
-        if self.instr[2].is_branch {
+        let mut info = result?;
+        info.mem_access += penalty;
+        if sfence {
+            self.itlb.flush();
+            self.dtlb.flush();
+        }
+        self.stat.num_inst += 1;
+        let mut mis_pred = false;
+        if info.is_branch {
             self.stat.num_branch += 1;
-            if !self.instr[2].taken_branch {
+            mis_pred = self.predictor.predict(pc, info.target) != info.taken_branch;
+            self.predictor.update(pc, info.taken_branch);
+            if mis_pred {
                 self.stat.num_mis_pred += 1;
             }
         }
-        self.stat.cycle += cycles;
+        if info.is_jump {
+            self.stat.num_jump += 1;
+        }
+        // fetch also has to guess where it goes
+        if info.taken_branch || info.is_jump {
+            let hit = self.targets.predict(pc, len, &info, self.pc);
+            if !hit && !mis_pred {
+                mis_pred = true;
+                self.stat.num_target_mis_pred += 1;
+            }
+        }
+        self.pipeline.issue(pc, info, fetch_stall, mis_pred, &mut self.stat);
+        if wfi {
+            self.wait();
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_alu_conformance() {
+    use crate::register::Reg;
+
+    // rd = x3, rs1 = x1, rs2 = x2
+    let r = |funct7: u32, funct3: u32, op: u32| {
+        (funct7 << 25) | (2 << 20) | (1 << 15) | (funct3 << 12) | (3 << 7) | op
+    };
+    let i = |imm: u64, funct3: u32, op: u32| {
+        ((imm as u32 & 0xfff) << 20) | (1 << 15) | (funct3 << 12) | (3 << 7) | op
+    };
+    const MAX: u64 = u64::MAX >> 1;
+    const MIN: u64 = i64::MIN as u64;
+    const NEG1: u64 = u64::MAX;
+    const MIN32: u64 = i32::MIN as u64;
+    let minus = |x: i64| x as u64;
+
+    let table: &[(&str, u32, u64, u64, u64)] = &[
+        ("ADD", r(0, 0, 0x33), MAX, 1, MIN),
+        ("ADD", r(0, 0, 0x33), NEG1, 1, 0),
+        ("SUB", r(0x20, 0, 0x33), 0, 1, NEG1),
+        ("SLL", r(0, 1, 0x33), 1, 65, 2),
+        ("SLT", r(0, 2, 0x33), NEG1, 0, 1),
+        ("SLTU", r(0, 3, 0x33), NEG1, 0, 0),
+        ("XOR", r(0, 4, 0x33), 0xf0, 0xff, 0x0f),
+        ("SRL", r(0, 5, 0x33), MIN, 63, 1),
+        ("SRA", r(0x20, 5, 0x33), MIN, 63, NEG1),
+        ("OR", r(0, 6, 0x33), 0xf0, 0x0f, 0xff),
+        ("AND", r(0, 7, 0x33), 0xf0, 0x0f, 0),
+        ("ADDI", i(NEG1, 0, 0x13), 0, NEG1, NEG1),
+        ("ADDI", i(1, 0, 0x13), NEG1, 1, 0),
+        ("SLTI", i(0, 2, 0x13), NEG1, 0, 1),
+        ("SLTIU", i(NEG1, 3, 0x13), 1, NEG1, 1),
+        ("XORI", i(NEG1, 4, 0x13), 0, NEG1, NEG1),
+        ("ORI", i(1, 6, 0x13), 0x100, 1, 0x101),
+        ("ANDI", i(0xf0, 7, 0x13), 0xff, 0xf0, 0xf0),
+        ("SLLI", i(63, 1, 0x13), 1, 63, MIN),
+        ("SRLI", i(63, 5, 0x13), MIN, 63, 1),
+        ("SRAI", i(0x400 | 63, 5, 0x13), MIN, 63, NEG1),
+        ("ADDIW", i(1, 0, 0x1b), 0x7fff_ffff, 1, MIN32),
+        ("SLLIW", i(31, 1, 0x1b), 1, 31, MIN32),
+        ("SRLIW", i(31, 5, 0x1b), 0x8000_0000, 31, 1),
+        ("SRLIW", i(0, 5, 0x1b), 0x8000_0000, 0, MIN32),
+        ("SRAIW", i(0x400 | 31, 5, 0x1b), 0x8000_0000, 31, NEG1),
+        ("ADDW", r(0, 0, 0x3b), 0x7fff_ffff, 1, MIN32),
+        ("SUBW", r(0x20, 0, 0x3b), MIN32, 1, 0x7fff_ffff),
+        ("SLLW", r(0, 1, 0x3b), 1, 31, MIN32),
+        ("SRLW", r(0, 5, 0x3b), 0x8000_0000, 31, 1),
+        ("SRAW", r(0x20, 5, 0x3b), 0x8000_0000, 31, NEG1),
+        ("MUL", r(1, 0, 0x33), NEG1, 2, minus(-2)),
+        ("MULH", r(1, 1, 0x33), NEG1, NEG1, 0),
+        ("MULH", r(1, 1, 0x33), MIN, MIN, 0x4000_0000_0000_0000),
+        ("MULHSU", r(1, 2, 0x33), NEG1, NEG1, NEG1),
+        ("MULHU", r(1, 3, 0x33), NEG1, NEG1, minus(-2)),
+        ("DIV", r(1, 4, 0x33), 20, 0, NEG1),
+        ("DIV", r(1, 4, 0x33), MIN, NEG1, MIN),
+        ("DIV", r(1, 4, 0x33), minus(-7), 2, minus(-3)),
+        ("DIVU", r(1, 5, 0x33), 20, 0, NEG1),
+        ("DIVU", r(1, 5, 0x33), NEG1, 2, MAX),
+        ("REM", r(1, 6, 0x33), 20, 0, 20),
+        ("REM", r(1, 6, 0x33), MIN, NEG1, 0),
+        ("REM", r(1, 6, 0x33), minus(-7), 2, minus(-1)),
+        ("REMU", r(1, 7, 0x33), 20, 0, 20),
+        ("REMU", r(1, 7, 0x33), 7, 2, 1),
+        ("MULW", r(1, 0, 0x3b), 0x1_0000, 0x1_0000, 0),
+        ("MULW", r(1, 0, 0x3b), 0x7fff_ffff, 2, minus(-2)),
+        ("DIVW", r(1, 4, 0x3b), 20, 0, NEG1),
+        ("DIVW", r(1, 4, 0x3b), MIN32, NEG1, MIN32),
+        ("DIVUW", r(1, 5, 0x3b), 20, 0, NEG1),
+        ("DIVUW", r(1, 5, 0x3b), 0xffff_ffff, 1, NEG1),
+        ("REMW", r(1, 6, 0x3b), 0x8000_0000, 0, MIN32),
+        ("REMW", r(1, 6, 0x3b), MIN32, NEG1, 0),
+        ("REMUW", r(1, 7, 0x3b), 0x8000_0005, 0, 0xffff_ffff_8000_0005),
+        ("REMUW", r(1, 7, 0x3b), 7, 2, 1),
+    ];
+
+    for (name, code, a, b, expected) in table {
+        let mut sim = Simulator::new();
+        sim.regs.set(Reg::from(1), *a);
+        sim.regs.set(Reg::from(2), *b);
+        let inst = action::matching(*code).unwrap();
+        assert!(format!("{:?}", inst).starts_with(&format!("{}(", name)),
+                "{:08x} decoded as {:?}", code, inst);
+        action::execute(&mut sim, inst, 4).unwrap();
+        assert_eq!(sim.regs.get(Reg::from(3)), *expected,
+                   "{} {:x}, {:x}", name, a, b);
     }
 }
+
+#[test]
+fn test_atomic() {
+    use crate::register::Reg;
+
+    // rd = x3, rs1 = x1, rs2 = x2
+    let a = |funct5: u32, funct3: u32| {
+        (funct5 << 27) | (2 << 20) | (1 << 15) | (funct3 << 12) | (3 << 7) | 0b0101111
+    };
+    let run = |sim: &mut Simulator, code: u32, address: u64, value: u64| {
+        sim.regs.set(Reg::from(1), address);
+        sim.regs.set(Reg::from(2), value);
+        let inst = action::matching(code).unwrap();
+        action::execute(sim, inst, 4).map(|info| (info, sim.regs.get(Reg::from(3))))
+    };
+    let mut sim = Simulator::new();
+    sim.memory.alloc(0x1000, 64, PERM_RW);
+    sim.memory.store_u64(0x1000, 5).unwrap();
+
+    // SC without a reservation fails and leaves memory alone
+    assert_eq!(run(&mut sim, a(0b00011, 3), 0x1000, 9).unwrap().1, 1);
+    assert_eq!(sim.memory.load_u64(0x1000), Ok(5));
+    assert_eq!(run(&mut sim, a(0b00010, 3), 0x1000, 0).unwrap().1, 5);
+    assert_eq!(run(&mut sim, a(0b00011, 3), 0x1000, 9).unwrap().1, 0);
+    assert_eq!(sim.memory.load_u64(0x1000), Ok(9));
+    // the reservation is consumed by the SC
+    assert_eq!(run(&mut sim, a(0b00011, 3), 0x1000, 7).unwrap().1, 1);
+
+    sim.memory.store_u32(0x1008, 0xffff_fffe).unwrap();
+    let (info, old) = run(&mut sim, a(0b00000, 2), 0x1008, 3).unwrap();
+    assert_eq!(old, (-2i64) as u64);
+    assert_eq!(sim.memory.load_u32(0x1008), Ok(1));
+    assert!(info.mem_access > 0);
+    run(&mut sim, a(0b10000, 2), 0x1008, (-4i64) as u64).unwrap();
+    assert_eq!(sim.memory.load_u32(0x1008), Ok(0xffff_fffc));
+    run(&mut sim, a(0b11100, 2), 0x1008, 5).unwrap();
+    assert_eq!(sim.memory.load_u32(0x1008), Ok(0xffff_fffc));
+    run(&mut sim, a(0b00001, 3), 0x1010, 42).unwrap();
+    assert_eq!(sim.memory.load_u64(0x1010), Ok(42));
+
+    assert_eq!(run(&mut sim, a(0b00000, 3), 0x1004, 1).err(),
+               Some(Exception::StoreAddressMisaligned(0x1004)));
+    assert_eq!(run(&mut sim, a(0b00010, 2), 0x1002, 0).err(),
+               Some(Exception::LoadAddressMisaligned(0x1002)));
+    assert_eq!(run(&mut sim, a(0b00000, 3), 0x2000, 1).err(),
+               Some(Exception::StoreAccessFault(0x2000)));
+}
+
+#[test]
+fn test_misaligned_policy() {
+    let mut sim = Simulator::new();
+    // lw a0, 2(a1) twice
+    sim.memory.alloc(0x1000, 16, PERM_RW | PERM_X);
+    sim.memory.store_u32(0x1000, 0x0025_a503).unwrap();
+    sim.memory.store_u32(0x1004, 0x0025_a503).unwrap();
+    sim.memory.store_u32(0x1008, 0x0403_0201).unwrap();
+    sim.regs.set_by_name("a1", 0x1008);
+    sim.memory.policy = Misaligned::Penalty(10);
+    sim.pc = 0x1000;
+    assert!(sim.run());
+    assert_eq!(sim.regs.get_by_name("a0"), 0x0403);
+    sim.pipeline.drain(&mut sim.stat);
+    assert!(sim.stat.cycle >= 10);
+    sim.memory.policy = Misaligned::Trap;
+    assert!(sim.run());
+    assert_eq!(sim.fault, Some(Exception::LoadAddressMisaligned(0x100a)));
+    assert_eq!(sim.stat.num_misaligned, 2);
+    assert_eq!(format!("{:?}", sim.stat.misaligned_pc), r#"{"1000": 1, "1004": 1}"#);
+    // split up, the upper half is on an unmapped page
+    sim.memory.policy = Misaligned::Emulate;
+    sim.regs.set_by_name("a1", 0x1ffc);
+    sim.fault = None;
+    sim.pc = 0x1004;
+    assert!(sim.run());
+    assert_eq!(sim.fault, Some(Exception::LoadAccessFault(0x1ffe)));
+}
+
+#[test]
+fn test_bss_segment() {
+    // a.out with nothing of its data segment in the file
+    use byteorder::{ByteOrder, LittleEndian};
+
+    let mut bytes = std::fs::read("objdump/test_obj/a.out").unwrap();
+    // the writable PT_LOAD among the program headers
+    let data = (0..LittleEndian::read_u16(&bytes[0x38..]) as usize)
+        .map(|i| 64 + 56 * i)
+        .find(|&x| LittleEndian::read_u32(&bytes[x..]) == PT_LOAD && bytes[x + 4] & 2 != 0)
+        .unwrap();
+    LittleEndian::write_u64(&mut bytes[data + 32..], 0);
+    let vaddr = LittleEndian::read_u64(&bytes[data + 16..]);
+    let path = std::env::temp_dir().join(format!("riscv-sim-bss-{}", std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+
+    let filename = path.to_str().unwrap();
+    let mut sim = Simulator::new();
+    assert_eq!(sim.load_from_elf(filename, &LoadConfig::new(filename)), Ok(()));
+    assert_eq!(sim.memory.load_u64(vaddr), Ok(0));
+    assert_eq!(sim.memory.store_u64(vaddr, 1), Ok(()));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_ecall_retires() {
+    let mut sim = Simulator::new();
+    // getpid twice, then read instret
+    sim.memory.alloc(0x1000, 12, PERM_RW | PERM_X);
+    sim.memory.store_u32(0x1000, 0x0000_0073).unwrap();
+    sim.memory.store_u32(0x1004, 0x0000_0073).unwrap();
+    sim.memory.store_u32(0x1008, 0xc020_2573).unwrap();
+    sim.regs.set_by_name("a7", 172);
+    sim.pc = 0x1000;
+    for _ in 0..3 {
+        assert!(sim.run());
+    }
+    assert_eq!(sim.stat.num_inst, 3);
+    assert_eq!(sim.regs.get_by_name("a0"), 2);
+}
+
+#[test]
+fn test_trap() {
+    let mut sim = Simulator::new();
+    // ld a0, 0(a1) from nowhere
+    sim.memory.alloc(0x1000, 4, PERM_RW | PERM_X);
+    sim.memory.store_u32(0x1000, 0x0005_b503).unwrap();
+    sim.regs.set_by_name("a1", 0x8000);
+    sim.csr.mtvec = 0x4000;
+    sim.pc = 0x1000;
+    assert!(sim.run());
+    assert_eq!(sim.pc, 0x4000);
+    assert_eq!((sim.csr.mepc, sim.csr.mcause, sim.csr.mtval), (0x1000, 5, 0x8000));
+    assert_eq!(sim.fault, None);
+
+    // without a handler the fault is reported and the run ends
+    sim.csr.mtvec = 0;
+    sim.pc = 0x1000;
+    assert!(sim.run());
+    assert_eq!(sim.fault, Some(Exception::LoadAccessFault(0x8000)));
+    assert_eq!(sim.fault_report(Exception::LoadAccessFault(0x8000)),
+               "load access fault (tval 8000) at pc 1000");
+    assert!(!sim.run());
+}
+
+#[test]
+fn test_itlb_per_fetch() {
+    use crate::csr::Privilege;
+    use crate::mmu::SATP_SV39;
+
+    let mut sim = Simulator::new();
+    // va 0x4000_0000 -> 1 GiB superpage at 0x8000_0000, RWX
+    sim.memory.alloc(0x8000_0000, 0x4000, PERM_RW | PERM_X);
+    sim.memory.store_u64(0x8000_0008, (0x8000_0000 >> 2) | 0b1100_1111).unwrap();
+    sim.csr.satp = SATP_SV39 << 60 | 0x8_0000;
+    sim.csr.pmp.write_addr(0, u64::MAX);
+    sim.csr.pmp.write_cfg(0, 0x1f);
+    sim.csr.privilege = Privilege::Supervisor;
+    // addi a0, a0, 1 twice, the second one across a page boundary
+    sim.memory.store_u32(0x8000_1000, 0x0015_0513).unwrap();
+    sim.memory.store_u16(0x8000_1ffe, 0x0513).unwrap();
+    sim.memory.store_u16(0x8000_2000, 0x0015).unwrap();
+    sim.pc = 0x4000_1000;
+    assert!(sim.run());
+    assert_eq!((sim.itlb.stats.num_access, sim.itlb.stats.num_miss), (1, 1));
+    sim.pc = 0x4000_1ffe;
+    assert!(sim.run());
+    assert_eq!((sim.itlb.stats.num_access, sim.itlb.stats.num_miss), (3, 2));
+    assert_eq!(sim.regs.get_by_name("a0"), 2);
+}
+
+#[test]
+fn test_compressed_fetch() {
+    let mut sim = Simulator::new();
+    // addi a0, zero, 5 split over two pages, then c.addi a0, 1
+    sim.memory.alloc(0x1ffe, 2, PERM_RW | PERM_X);
+    sim.memory.alloc(0x2000, 4, PERM_RW | PERM_X);
+    sim.memory.store_u16(0x1ffe, 0x0513).unwrap();
+    sim.memory.store_u16(0x2000, 0x0050).unwrap();
+    sim.memory.store_u16(0x2002, 0x0505).unwrap();
+    sim.pc = 0x1ffe;
+    assert!(sim.run());
+    assert_eq!((sim.pc, sim.regs.get_by_name("a0")), (0x2002, 5));
+    assert!(sim.run());
+    assert_eq!((sim.pc, sim.regs.get_by_name("a0")), (0x2004, 6));
+    // the upper half is on an unmapped page
+    sim.memory.store_u16(0x2ffe, 0x0513).unwrap();
+    sim.pc = 0x2ffe;
+    assert!(sim.run());
+    assert_eq!(sim.fault, Some(Exception::InstructionAccessFault(0x3000)));
+}
+
+#[test]
+fn test_initial_stack() {
+    let mut sim = Simulator::new();
+    let mut config = LoadConfig::new("prog");
+    config.stack_size = 4096;
+    sim.memory.alloc(config.stack_top - config.stack_size, config.stack_size as usize, PERM_RW);
+    config.args.push("x".to_string());
+    config.env.push("A=1".to_string());
+    let (sp, argv, envp) = sim.push_arguments(&Elf::default(), &config).unwrap();
+
+    let string = |sim: &Simulator, address: u64| {
+        let mut s = String::new();
+        let mut p = sim.memory.load_u64(address).unwrap();
+        while let Ok(c) = sim.memory.load_u8(p) {
+            if c == 0 {
+                break;
+            }
+            s.push(c as char);
+            p += 1;
+        }
+        s
+    };
+    assert_eq!(sp & 0xf, 0);
+    assert_eq!(sim.memory.load_u64(sp), Ok(2));
+    assert_eq!(argv, sp + 8);
+    assert_eq!(string(&sim, argv), "prog");
+    assert_eq!(string(&sim, argv + 8), "x");
+    assert_eq!(sim.memory.load_u64(argv + 16), Ok(0));
+    assert_eq!(envp, argv + 24);
+    assert_eq!(string(&sim, envp), "A=1");
+    assert_eq!(sim.memory.load_u64(envp + 8), Ok(0));
+    assert_eq!(sim.memory.load_u64(envp + 16), Ok(AT_PHDR));
+}
+
+#[test]
+fn test_load_limits() {
+    let filename = "objdump/test_obj/a.out";
+    // its segments end at 0x11c68
+    let mut config = LoadConfig::new(filename);
+    config.stack_top = 0x11_0000;
+    config.stack_size = 0x1000;
+    assert!(Simulator::new().load_from_elf(filename, &config).is_err());
+
+    let mut sim = Simulator::new();
+    config.stack_top = 0x40_0000;
+    config.heap_size = 0x2000;
+    sim.load_from_elf(filename, &config).unwrap();
+    let brk = |sim: &mut Simulator, address| {
+        sim.kernel.syscall(&mut sim.memory, 214, [address, 0, 0, 0])
+    };
+    assert_eq!(brk(&mut sim, 0), 0x12000);
+    let limit = 0x11c68 + 0x2000;
+    assert_eq!(brk(&mut sim, limit), limit);
+    assert_eq!(sim.memory.store_u8(limit - 1, 1), Ok(()));
+    // past heap_size the break stays where it is
+    assert_eq!(brk(&mut sim, limit + 1), limit);
+
+    // just below the stack is the guard
+    let x = sim.stack_guard.end - 8;
+    sim.pc = 0x100b0;
+    sim.trap(Exception::StoreAccessFault(x));
+    assert_eq!(sim.fault, Some(Exception::StoreAccessFault(x)));
+    assert!(sim.fault_report(Exception::StoreAccessFault(x)).starts_with("stack overflow"));
+    assert!(!sim.fault_report(Exception::StoreAccessFault(limit)).starts_with("stack overflow"));
+
+    // RAM beyond the program, executable throughout
+    let mut sim = Simulator::new();
+    config.ram = Some((VIRT_RAM_BASE, VIRT_RAM_SIZE));
+    sim.load_from_elf(filename, &config).unwrap();
+    let last = VIRT_RAM_BASE + VIRT_RAM_SIZE - 8;
+    assert_eq!(sim.memory.store_u64(last, 0x13), Ok(()));
+    assert_eq!(sim.memory.fetch_u16(last), Ok(0x13));
+    assert!(sim.memory.load_u8(last + 8).is_err());
+}
+
+#[test]
+fn test_time() {
+    use crate::device::CLINT_BASE;
+
+    let mut sim = Simulator::new();
+    sim.memory.bus_mut().attach_virt(Box::new(std::io::empty()), Box::new(std::io::sink()));
+    // sd a1, 0(a2); csrr a0, time
+    sim.memory.alloc(0x1000, 8, PERM_RW | PERM_X);
+    sim.memory.store_u32(0x1000, 0x00b6_3023).unwrap();
+    sim.memory.store_u32(0x1004, 0xc010_2573).unwrap();
+    sim.regs.set_by_name("a1", 1 << 40);
+    sim.regs.set_by_name("a2", CLINT_BASE + 0xbff8);
+    sim.pc = 0x1000;
+    assert!(sim.run());
+    let cycle = sim.stat.cycle;
+    assert!(sim.run());
+    // time is mtime, not the cycle count
+    let time = sim.regs.get_by_name("a0");
+    assert!(time >= 1 << 40 && time - (1 << 40) <= sim.stat.cycle - cycle);
+}
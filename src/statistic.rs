@@ -1,3 +1,19 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Error};
+
+// how often something happened at each pc, printed hottest first
+#[derive(Default)]
+pub struct PcCounts(HashMap<u64, u64>);
+
+impl Debug for PcCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let mut counts: Vec<_> = self.0.iter().collect();
+        counts.sort_by_key(|(pc, n)| (std::cmp::Reverse(**n), **pc));
+        f.debug_map()
+            .entries(counts.iter().take(10).map(|(pc, n)| (format!("{:x}", pc), n)))
+            .finish()
+    }
+}
 
 #[derive(Default, Debug)]
 pub struct Statistic {
@@ -5,7 +21,14 @@ pub struct Statistic {
     pub num_inst: u64,
     pub num_branch: u64,
     pub num_mis_pred: u64,
+    pub num_target_mis_pred: u64,
+    pub num_jump: u64,
     pub num_data_hazard: u64,
+    pub num_data_stall: u64,
+    pub num_control_stall: u64,
+    pub num_misaligned: u64,
+    pub misaligned_pc: PcCounts,
+    pub num_interrupt: u64,
     prediction_accuracy: f32,
 }
 
@@ -14,12 +37,18 @@ impl Statistic {
         (self.cycle as f32) / (self.num_inst as f32)
     }
 
+    pub fn record_misaligned(&mut self, pc: u64) {
+        self.num_misaligned += 1;
+        *self.misaligned_pc.0.entry(pc).or_insert(0) += 1;
+    }
+
     pub fn get_pred_accuracy(&self) -> f32 {
-        (self.num_mis_pred as f32) / (self.num_branch as f32)
+        1.0 - (self.num_mis_pred as f32) / (self.num_branch as f32)
     }
 
     pub fn println(&mut self) {
-        self.prediction_accuracy = 1.0 - self.num_mis_pred as f32 / self.num_branch as f32;
+        self.prediction_accuracy = self.get_pred_accuracy();
         println!("{:#?}", self);
+        println!("CPI: {}", self.get_cpi());
     }
 }
@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::memory::{Memory, PERM_RW};
+use crate::register::RegisterFile;
+use crate::trap::Exception;
+
+// RV64 Linux syscall numbers (asm-generic), plus the legacy ones used by newlib
+const SYS_GETCWD: u64 = 17;
+const SYS_IOCTL: u64 = 29;
+const SYS_UNLINKAT: u64 = 35;
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_WRITEV: u64 = 66;
+const SYS_READLINKAT: u64 = 78;
+const SYS_FSTATAT: u64 = 79;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_SET_TID_ADDRESS: u64 = 96;
+const SYS_SET_ROBUST_LIST: u64 = 99;
+const SYS_CLOCK_GETTIME: u64 = 113;
+const SYS_RT_SIGACTION: u64 = 134;
+const SYS_RT_SIGPROCMASK: u64 = 135;
+const SYS_TIMES: u64 = 153;
+const SYS_UNAME: u64 = 160;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_GETPID: u64 = 172;
+const SYS_GETUID: u64 = 174;
+const SYS_GETEUID: u64 = 175;
+const SYS_GETGID: u64 = 176;
+const SYS_GETEGID: u64 = 177;
+const SYS_GETTID: u64 = 178;
+const SYS_BRK: u64 = 214;
+const SYS_MPROTECT: u64 = 226;
+const SYS_PRLIMIT64: u64 = 261;
+const SYS_GETRANDOM: u64 = 278;
+const SYS_OPEN: u64 = 1024;
+const SYS_UNLINK: u64 = 1026;
+const SYS_STAT: u64 = 1038;
+
+const EPERM: u64 = 1;
+const EBADF: u64 = 9;
+const EACCES: u64 = 13;
+const EFAULT: u64 = 14;
+const EINVAL: u64 = 22;
+const ENOTTY: u64 = 25;
+const ENOSYS: u64 = 38;
+
+const AT_FDCWD: u64 = -100i64 as u64;
+const AT_EMPTY_PATH: u64 = 0x1000;
+
+const O_ACCMODE: u64 = 0o3;
+const O_WRONLY: u64 = 0o1;
+const O_RDWR: u64 = 0o2;
+const O_CREAT: u64 = 0o100;
+const O_EXCL: u64 = 0o200;
+const O_TRUNC: u64 = 0o1000;
+const O_APPEND: u64 = 0o2000;
+
+const S_IFCHR: u32 = 0o020000;
+const KERNEL_STAT_SIZE: usize = 128;
+const PAGE_SIZE: u64 = 4096;
+
+/// A proxy kernel in the spirit of riscv-pk: system calls issued by the
+/// guest with ECALL are serviced against the host. File paths are resolved
+/// inside `root` and cannot escape it.
+pub struct ProxyKernel {
+    pub root: PathBuf,
+    pub exit_code: Option<u64>,
+    files: HashMap<u64, File>,
+    // the guest paths of the files opened, for the *at calls
+    paths: HashMap<u64, String>,
+    next_fd: u64,
+    brk_start: u64,
+    brk: u64,
+    heap_end: u64,
+    heap_limit: u64,
+}
+
+fn error(e: io::Error) -> u64 {
+    (-(e.raw_os_error().unwrap_or(EINVAL as i32) as i64)) as u64
+}
+
+fn errno(e: u64) -> u64 {
+    (-(e as i64)) as u64
+}
+
+fn page_align(address: u64) -> u64 {
+    (address + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+// bytes a single read or write moves at most, larger ones come back short
+const MAX_TRANSFER: u64 = 64 << 10;
+
+fn read_bytes(m: &Memory, address: u64, size: usize) -> Result<Vec<u8>, Exception> {
+    (0..size as u64).map(|i| m.load_u8(address + i)).collect()
+}
+
+fn write_bytes(m: &mut Memory, address: u64, bytes: &[u8]) -> Result<(), Exception> {
+    for (i, b) in bytes.iter().enumerate() {
+        m.store_u8(address + i as u64, *b)?;
+    }
+    Ok(())
+}
+
+fn read_string(m: &Memory, address: u64) -> Result<String, Exception> {
+    let mut bytes = Vec::new();
+    let mut address = address;
+    loop {
+        let b = m.load_u8(address)?;
+        if b == 0 {
+            break;
+        }
+        bytes.push(b);
+        address += 1;
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+impl ProxyKernel {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            exit_code: None,
+            files: HashMap::new(),
+            paths: HashMap::new(),
+            next_fd: 3,
+            brk_start: 0,
+            brk: 0,
+            heap_end: 0,
+            heap_limit: 0,
+        }
+    }
+
+    // the program break starts right after the highest loaded segment
+    // and can not go past `limit`
+    pub fn init_brk(&mut self, address: u64, limit: u64) {
+        self.brk_start = page_align(address);
+        self.brk = self.brk_start;
+        self.heap_end = self.brk_start;
+        self.heap_limit = limit;
+    }
+
+    // the guest reads its standard input from `f` instead of the host's
+    pub fn redirect_stdin(&mut self, f: File) {
+        self.files.insert(0, f);
+    }
+
+    pub fn exited(&self) -> bool {
+        self.exit_code.is_some()
+    }
+
+    pub fn ecall(&mut self, r: &mut RegisterFile, m: &mut Memory) {
+        let args = ["a0", "a1", "a2", "a3"].map(|x| r.get_by_name(x));
+        let result = self.syscall(m, r.get_by_name("a7"), args);
+        r.set_by_name("a0", result);
+    }
+
+    // returns what the guest gets in a0
+    pub fn syscall(&mut self, m: &mut Memory, which: u64, args: [u64; 4]) -> u64 {
+        let [a0, a1, a2, a3] = args;
+        let result = match which {
+            SYS_EXIT | SYS_EXIT_GROUP => {
+                self.exit_code = Some(a0);
+                Ok(0)
+            }
+            SYS_READ => self.read(m, a0, a1, a2),
+            SYS_WRITE => self.write(m, a0, a1, a2),
+            SYS_WRITEV => self.writev(m, a0, a1, a2),
+            SYS_OPENAT => self.open(m, a0, a1, a2, a3),
+            SYS_OPEN => self.open(m, AT_FDCWD, a0, a1, a2),
+            SYS_CLOSE => Ok(self.close(a0)),
+            SYS_LSEEK => Ok(self.lseek(a0, a1, a2)),
+            SYS_FSTAT => self.fstat(m, a0, a1),
+            SYS_FSTATAT => self.fstatat(m, a0, a1, a2, a3),
+            SYS_STAT => self.fstatat(m, AT_FDCWD, a0, a1, 0),
+            SYS_UNLINKAT => self.unlink(m, a1),
+            SYS_UNLINK => self.unlink(m, a0),
+            SYS_BRK => Ok(self.brk(m, a0)),
+            SYS_GETTIMEOFDAY => self.gettimeofday(m, a0),
+            SYS_CLOCK_GETTIME => self.clock_gettime(m, a1),
+            SYS_UNAME => self.uname(m, a0),
+            SYS_GETCWD => self.getcwd(m, a0, a1),
+            SYS_GETRANDOM => self.getrandom(m, a0, a1),
+            SYS_IOCTL => Ok(if a0 <= 2 { errno(ENOTTY) } else { errno(EINVAL) }),
+            SYS_TIMES | SYS_SET_ROBUST_LIST | SYS_RT_SIGACTION
+            | SYS_RT_SIGPROCMASK | SYS_MPROTECT | SYS_PRLIMIT64 => Ok(0),
+            SYS_READLINKAT => Ok(errno(ENOSYS)),
+            SYS_GETUID | SYS_GETEUID | SYS_GETGID | SYS_GETEGID => Ok(0),
+            SYS_GETPID | SYS_GETTID | SYS_SET_TID_ADDRESS => Ok(1),
+            _ => {
+                eprintln!("unknown syscall {}", which);
+                Ok(errno(ENOSYS))
+            }
+        };
+        // a bad guest pointer is reported to the program, not trapped
+        result.unwrap_or(errno(EFAULT))
+    }
+
+    // path relative to the sandbox root; neither `..` nor a symlink can
+    // lead out of it
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut result = self.root.clone();
+        let mut depth = 0;
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(s) => {
+                    result.push(s);
+                    depth += 1;
+                }
+                Component::ParentDir => {
+                    if depth == 0 {
+                        return None;
+                    }
+                    result.pop();
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        // the part that does not exist yet can not be a symlink
+        let mut missing = Vec::new();
+        let mut existing = result.as_path();
+        let mut real = loop {
+            match existing.canonicalize() {
+                Ok(real) => break real,
+                Err(_) => {
+                    missing.push(existing.file_name()?);
+                    existing = existing.parent()?;
+                }
+            }
+        };
+        missing.iter().rev().for_each(|x| real.push(x));
+        match real.starts_with(self.root.canonicalize().ok()?) {
+            true => Some(real),
+            false => None,
+        }
+    }
+
+    // `path` seen from the directory open as `dirfd`, the working
+    // directory being the root
+    fn at(&self, dirfd: u64, path: String) -> Option<String> {
+        if dirfd == AT_FDCWD || path.starts_with('/') {
+            return Some(path);
+        }
+        self.paths.get(&dirfd).map(|dir| format!("{}/{}", dir, path))
+    }
+
+    fn read(&mut self, m: &mut Memory, fd: u64, buf: u64, count: u64) -> Result<u64, Exception> {
+        let mut bytes = vec![0; count.min(MAX_TRANSFER) as usize];
+        let result = match self.files.get_mut(&fd) {
+            Some(f) => f.read(&mut bytes),
+            None if fd == 0 => io::stdin().read(&mut bytes),
+            None => return Ok(errno(EBADF)),
+        };
+        match result {
+            Ok(n) => {
+                write_bytes(m, buf, &bytes[..n])?;
+                Ok(n as u64)
+            }
+            Err(e) => Ok(error(e)),
+        }
+    }
+
+    fn write(&mut self, m: &mut Memory, fd: u64, buf: u64, count: u64) -> Result<u64, Exception> {
+        let count = count.min(MAX_TRANSFER);
+        let bytes = read_bytes(m, buf, count as usize)?;
+        let result = match fd {
+            1 => io::stdout().write_all(&bytes).and_then(|_| io::stdout().flush()),
+            2 => io::stderr().write_all(&bytes),
+            _ => match self.files.get_mut(&fd) {
+                Some(f) => f.write_all(&bytes),
+                None => return Ok(errno(EBADF)),
+            },
+        };
+        match result {
+            Ok(_) => Ok(count),
+            Err(e) => Ok(error(e)),
+        }
+    }
+
+    fn writev(&mut self, m: &mut Memory, fd: u64, iov: u64, iovcnt: u64) -> Result<u64, Exception> {
+        let mut total = 0;
+        for i in 0..iovcnt {
+            let base = m.load_u64(iov + i * 16)?;
+            let len = m.load_u64(iov + i * 16 + 8)?;
+            let n = self.write(m, fd, base, len)?;
+            if (n as i64) < 0 {
+                return Ok(n);
+            }
+            total += n;
+            if n < len {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    fn open(&mut self, m: &mut Memory, dirfd: u64, path: u64, flags: u64, mode: u64)
+            -> Result<u64, Exception> {
+        let path = match self.at(dirfd, read_string(m, path)?) {
+            Some(path) => path,
+            None => return Ok(errno(EBADF)),
+        };
+        let host = match self.resolve(&path) {
+            Some(host) => host,
+            None => return Ok(errno(EACCES)),
+        };
+        let mut options = OpenOptions::new();
+        match flags & O_ACCMODE {
+            O_WRONLY => options.write(true),
+            O_RDWR => options.read(true).write(true),
+            _ => options.read(true),
+        };
+        options.append(flags & O_APPEND != 0)
+            .truncate(flags & O_TRUNC != 0);
+        if flags & O_CREAT != 0 {
+            if flags & O_EXCL != 0 {
+                options.create_new(true);
+            } else {
+                options.create(true);
+            }
+            options.mode(mode as u32);
+        }
+        match options.open(host) {
+            Ok(f) => {
+                let fd = self.next_fd;
+                self.next_fd += 1;
+                self.files.insert(fd, f);
+                self.paths.insert(fd, path);
+                Ok(fd)
+            }
+            Err(e) => Ok(error(e)),
+        }
+    }
+
+    fn close(&mut self, fd: u64) -> u64 {
+        if fd <= 2 {
+            return 0;
+        }
+        self.paths.remove(&fd);
+        match self.files.remove(&fd) {
+            Some(_) => 0,
+            None => errno(EBADF),
+        }
+    }
+
+    fn lseek(&mut self, fd: u64, offset: u64, whence: u64) -> u64 {
+        let f = match self.files.get_mut(&fd) {
+            Some(f) => f,
+            None => return errno(EBADF),
+        };
+        let pos = match whence {
+            0 => SeekFrom::Start(offset),
+            1 => SeekFrom::Current(offset as i64),
+            2 => SeekFrom::End(offset as i64),
+            _ => return errno(EINVAL),
+        };
+        match f.seek(pos) {
+            Ok(n) => n,
+            Err(e) => error(e),
+        }
+    }
+
+    fn fstat(&mut self, m: &mut Memory, fd: u64, buf: u64) -> Result<u64, Exception> {
+        if fd <= 2 && !self.files.contains_key(&fd) {
+            // a character device, so that the libc line-buffers the console
+            let mut st = [0u8; KERNEL_STAT_SIZE];
+            st[16..20].copy_from_slice(&(S_IFCHR | 0o620).to_le_bytes());
+            st[20..24].copy_from_slice(&1u32.to_le_bytes());
+            st[56..60].copy_from_slice(&1024u32.to_le_bytes());
+            write_bytes(m, buf, &st)?;
+            return Ok(0);
+        }
+        match self.files.get(&fd).map(|f| f.metadata()) {
+            Some(Ok(meta)) => {
+                write_bytes(m, buf, &kernel_stat(&meta))?;
+                Ok(0)
+            }
+            Some(Err(e)) => Ok(error(e)),
+            None => Ok(errno(EBADF)),
+        }
+    }
+
+    // glibc's fstat is fstatat with an empty path and AT_EMPTY_PATH
+    fn fstatat(&mut self, m: &mut Memory, dirfd: u64, path: u64, buf: u64, flags: u64)
+               -> Result<u64, Exception> {
+        let path = read_string(m, path)?;
+        if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            return self.fstat(m, dirfd, buf);
+        }
+        let path = match self.at(dirfd, path) {
+            Some(path) => path,
+            None => return Ok(errno(EBADF)),
+        };
+        let host = match self.resolve(&path) {
+            Some(host) => host,
+            None => return Ok(errno(EACCES)),
+        };
+        match std::fs::metadata(host) {
+            Ok(meta) => {
+                write_bytes(m, buf, &kernel_stat(&meta))?;
+                Ok(0)
+            }
+            Err(e) => Ok(error(e)),
+        }
+    }
+
+    fn unlink(&mut self, m: &mut Memory, path: u64) -> Result<u64, Exception> {
+        let path = read_string(m, path)?;
+        Ok(match self.resolve(&path) {
+            Some(host) => match std::fs::remove_file(host) {
+                Ok(_) => 0,
+                Err(e) => error(e),
+            },
+            None => errno(EPERM),
+        })
+    }
+
+    // like Linux, a failed request returns the current break
+    fn brk(&mut self, m: &mut Memory, address: u64) -> u64 {
+        if address < self.brk_start || address > self.heap_limit {
+            return self.brk;
+        }
+        let end = page_align(address);
+        if end > self.heap_end {
+            m.alloc(self.heap_end, (end - self.heap_end) as usize, PERM_RW);
+            self.heap_end = end;
+        }
+        self.brk = address;
+        self.brk
+    }
+
+    fn gettimeofday(&mut self, m: &mut Memory, tv: u64) -> Result<u64, Exception> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        m.store_u64(tv, now.as_secs())?;
+        m.store_u64(tv + 8, now.subsec_micros() as u64)?;
+        Ok(0)
+    }
+
+    fn clock_gettime(&mut self, m: &mut Memory, tp: u64) -> Result<u64, Exception> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        m.store_u64(tp, now.as_secs())?;
+        m.store_u64(tp + 8, now.subsec_nanos() as u64)?;
+        Ok(0)
+    }
+
+    fn uname(&mut self, m: &mut Memory, buf: u64) -> Result<u64, Exception> {
+        // struct utsname: six fields of 65 bytes each
+        let fields = ["Linux", "riscv-sim", "5.0.0", "#1", "riscv64", ""];
+        for (i, field) in fields.iter().enumerate() {
+            let mut bytes = [0u8; 65];
+            bytes[..field.len()].copy_from_slice(field.as_bytes());
+            write_bytes(m, buf + i as u64 * 65, &bytes)?;
+        }
+        Ok(0)
+    }
+
+    fn getcwd(&mut self, m: &mut Memory, buf: u64, size: u64) -> Result<u64, Exception> {
+        if size < 2 {
+            return Ok(errno(EINVAL));
+        }
+        write_bytes(m, buf, b"/\0")?;
+        Ok(buf)
+    }
+
+    fn getrandom(&mut self, m: &mut Memory, buf: u64, len: u64) -> Result<u64, Exception> {
+        // deterministic on purpose, so that runs are reproducible
+        let mut x: u64 = 0x2545_f491_4f6c_dd1d;
+        for i in 0..len {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            m.store_u8(buf + i, x as u8)?;
+        }
+        Ok(len)
+    }
+}
+
+// struct stat as laid out by the RV64 Linux kernel
+fn kernel_stat(meta: &std::fs::Metadata) -> [u8; KERNEL_STAT_SIZE] {
+    let mut st = [0u8; KERNEL_STAT_SIZE];
+    st[0..8].copy_from_slice(&meta.dev().to_le_bytes());
+    st[8..16].copy_from_slice(&meta.ino().to_le_bytes());
+    st[16..20].copy_from_slice(&meta.mode().to_le_bytes());
+    st[20..24].copy_from_slice(&(meta.nlink() as u32).to_le_bytes());
+    st[24..28].copy_from_slice(&meta.uid().to_le_bytes());
+    st[28..32].copy_from_slice(&meta.gid().to_le_bytes());
+    st[32..40].copy_from_slice(&meta.rdev().to_le_bytes());
+    st[48..56].copy_from_slice(&meta.size().to_le_bytes());
+    st[56..60].copy_from_slice(&(meta.blksize() as u32).to_le_bytes());
+    st[64..72].copy_from_slice(&meta.blocks().to_le_bytes());
+    st[72..80].copy_from_slice(&meta.atime().to_le_bytes());
+    st[80..88].copy_from_slice(&meta.atime_nsec().to_le_bytes());
+    st[88..96].copy_from_slice(&meta.mtime().to_le_bytes());
+    st[96..104].copy_from_slice(&meta.mtime_nsec().to_le_bytes());
+    st[104..112].copy_from_slice(&meta.ctime().to_le_bytes());
+    st[112..120].copy_from_slice(&meta.ctime_nsec().to_le_bytes());
+    st
+}
+
+#[test]
+fn test_resolve() {
+    let root = std::env::temp_dir().join(format!("riscv-sim-root-{}", std::process::id()));
+    std::fs::create_dir(&root).unwrap();
+    let root = root.canonicalize().unwrap();
+    std::os::unix::fs::symlink("/etc", root.join("out")).unwrap();
+    std::os::unix::fs::symlink(".", root.join("here")).unwrap();
+    let k = ProxyKernel::new(root.clone());
+    assert_eq!(k.resolve("/a/b.txt"), Some(root.join("a/b.txt")));
+    assert_eq!(k.resolve("a/../b"), Some(root.join("b")));
+    assert_eq!(k.resolve("../etc/passwd"), None);
+    assert_eq!(k.resolve("/a/../../etc"), None);
+    // symlinks are followed, but only as far as the root
+    assert_eq!(k.resolve("here/c"), Some(root.join("c")));
+    assert_eq!(k.resolve("out/passwd"), None);
+    assert_eq!(k.resolve("out"), None);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_fstatat() {
+    use crate::memory::PERM_RW;
+
+    let root = std::env::temp_dir().join(format!("riscv-sim-stat-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("d")).unwrap();
+    std::fs::write(root.join("d/f"), b"hello").unwrap();
+    let mut k = ProxyKernel::new(root.clone());
+    let mut m = Memory::new();
+    m.alloc(0x10000, 0x1000, PERM_RW);
+    let (path, buf) = (0x10000, 0x10800);
+    let size = |m: &Memory| m.load_u64(buf + 48).unwrap();
+    let mode = |m: &Memory| m.load_u32(buf + 16).unwrap();
+
+    write_bytes(&mut m, path, b"d\0").unwrap();
+    let dir = k.syscall(&mut m, SYS_OPENAT, [AT_FDCWD, path, 0, 0]);
+    write_bytes(&mut m, path, b"f\0").unwrap();
+    assert_eq!(k.syscall(&mut m, SYS_FSTATAT, [dir, path, buf, 0]), 0);
+    assert_eq!(size(&m), 5);
+    let file = k.syscall(&mut m, SYS_OPENAT, [dir, path, 0, 0]);
+    assert_eq!(k.syscall(&mut m, SYS_FSTATAT, [AT_FDCWD, path, buf, 0]), errno(2)); // ENOENT
+    // fstat as glibc does it
+    write_bytes(&mut m, path, b"\0").unwrap();
+    assert_eq!(k.syscall(&mut m, SYS_FSTATAT, [file, path, buf, AT_EMPTY_PATH]), 0);
+    assert_eq!(size(&m), 5);
+    assert_eq!(k.syscall(&mut m, SYS_FSTATAT, [1, path, buf, AT_EMPTY_PATH]), 0);
+    assert_eq!(mode(&m) & 0o170000, S_IFCHR);
+    assert_eq!(k.syscall(&mut m, SYS_FSTATAT, [42, path, buf, AT_EMPTY_PATH]), errno(EBADF));
+
+    // writes are capped like reads
+    m.alloc(0x20000, 2 * MAX_TRANSFER as usize, PERM_RW);
+    write_bytes(&mut m, path, b"d/g\0").unwrap();
+    let out = k.syscall(&mut m, SYS_OPENAT, [AT_FDCWD, path, O_WRONLY | O_CREAT, 0o644]);
+    assert_eq!(k.syscall(&mut m, SYS_WRITE, [out, 0x20000, 2 * MAX_TRANSFER, 0]), MAX_TRANSFER);
+    assert_eq!(std::fs::metadata(root.join("d/g")).unwrap().len(), MAX_TRANSFER);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_read_limit() {
+    use crate::memory::PERM_RW;
+
+    let path = std::env::temp_dir().join(format!("riscv-sim-read-{}", std::process::id()));
+    std::fs::write(&path, vec![7; 2 * MAX_TRANSFER as usize]).unwrap();
+    let mut k = ProxyKernel::new(PathBuf::from("."));
+    k.redirect_stdin(File::open(&path).unwrap());
+    let mut m = Memory::new();
+    m.alloc(0x10000, 2 * MAX_TRANSFER as usize, PERM_RW);
+    // a huge count is a short read, not a huge allocation
+    assert_eq!(k.read(&mut m, 0, 0x10000, 0xffff_ffff_ffff), Ok(MAX_TRANSFER));
+    assert_eq!(m.load_u8(0x10000 + MAX_TRANSFER - 1), Ok(7));
+    assert_eq!(m.load_u8(0x10000 + MAX_TRANSFER), Ok(0));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,173 @@
+use crate::cache::{CacheOp, Duration, Storage};
+use crate::mmu::Walk;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Replacement {
+    Lru,
+    Fifo,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TlbConfig {
+    pub name: &'static str,
+    pub entries: u64,
+    pub associativity: u64,
+    pub replacement: Replacement,
+    // cycles charged on a miss, or None to replay the page table walk
+    // through the caches
+    pub miss_penalty: Option<Duration>,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct TlbStats {
+    pub num_access: u64,
+    pub num_miss: u64,
+    pub stall: Duration,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+struct TlbEntry {
+    is_valid: bool,
+    page: u64,
+    satp: u64,
+    // last use for LRU, insertion for FIFO
+    stamp: u64,
+}
+
+/// A timing model only: translation itself always walks the page table,
+/// the TLB decides how much that walk costs.
+pub struct Tlb {
+    config: TlbConfig,
+    pub stats: TlbStats,
+    sets: Vec<Vec<TlbEntry>>,
+    clock: u64,
+    seed: u64,
+}
+
+impl Tlb {
+    pub fn new(config: TlbConfig) -> Self {
+        assert!(config.associativity > 0 && config.entries.is_multiple_of(config.associativity));
+        let num_sets = config.entries / config.associativity;
+        assert!(num_sets.is_power_of_two());
+        Self {
+            config,
+            stats: Default::default(),
+            sets: vec![vec![TlbEntry::default(); config.associativity as usize]; num_sets as usize],
+            clock: 0,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    // xorshift, so that runs are reproducible
+    fn random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    // returns the cycles spent on top of the memory access itself
+    pub fn access(&mut self, walk: &Walk, cache: &mut Box<dyn Storage>) -> Duration {
+        self.clock += 1;
+        self.stats.num_access += 1;
+        let index = (walk.page % self.sets.len() as u64) as usize;
+        let (clock, lru) = (self.clock, self.config.replacement == Replacement::Lru);
+        if let Some(entry) = self.sets[index].iter_mut()
+            .find(|x| x.is_valid && x.page == walk.page && x.satp == walk.satp) {
+            if lru {
+                entry.stamp = clock;
+            }
+            return 0;
+        }
+
+        self.stats.num_miss += 1;
+        let victim = match self.sets[index].iter().position(|x| !x.is_valid) {
+            Some(i) => i,
+            None if self.config.replacement == Replacement::Random =>
+                (self.random() % self.config.associativity) as usize,
+            None => self.sets[index].iter().enumerate()
+                .min_by_key(|(_, x)| x.stamp)
+                .map(|(i, _)| i)
+                .unwrap(),
+        };
+        self.sets[index][victim] = TlbEntry {
+            is_valid: true,
+            page: walk.page,
+            satp: walk.satp,
+            stamp: clock,
+        };
+        let stall = match self.config.miss_penalty {
+            Some(penalty) => penalty,
+            None => walk.ptes.iter().map(|x| cache.access(*x, CacheOp::Read)).sum(),
+        };
+        self.stats.stall += stall;
+        stall
+    }
+
+    // SFENCE.VMA
+    pub fn flush(&mut self) {
+        self.sets.iter_mut().flatten().for_each(|x| x.is_valid = false);
+    }
+
+    pub fn output_stats(&self) {
+        println!("{}:", self.config.name);
+        println!("  {:?}", self.stats);
+        println!("  miss rate: {}", self.stats.num_miss as f32 / self.stats.num_access as f32);
+    }
+}
+
+pub fn default_itlb() -> Tlb {
+    Tlb::new(TlbConfig {
+        name: "ITLB",
+        entries: 32,
+        associativity: 32,
+        replacement: Replacement::Lru,
+        miss_penalty: None,
+    })
+}
+
+pub fn default_dtlb() -> Tlb {
+    Tlb::new(TlbConfig {
+        name: "DTLB",
+        entries: 64,
+        associativity: 4,
+        replacement: Replacement::Lru,
+        miss_penalty: None,
+    })
+}
+
+#[test]
+fn test_tlb() {
+    let mut cache = crate::cache::new_1_levels(crate::cache::CacheConfig {
+        name: "L1",
+        write_through: false,
+        write_allocate: true,
+        capacity: 1024,
+        associativity: 2,
+        line_size: 64,
+        latency: 1,
+    });
+    let mut tlb = Tlb::new(TlbConfig {
+        name: "test",
+        entries: 4,
+        associativity: 2,
+        replacement: Replacement::Lru,
+        miss_penalty: None,
+    });
+    let walk = |page| Walk { page, satp: 1, ptes: vec![0x1000, 0x2000, 0x3000] };
+    // three PTE reads, each missing L1
+    assert_eq!(tlb.access(&walk(0), &mut cache), 33);
+    assert_eq!(tlb.access(&walk(0), &mut cache), 0);
+    tlb.access(&walk(2), &mut cache);
+    tlb.access(&walk(0), &mut cache);
+    // evicts page 2, the least recently used in set 0
+    tlb.access(&walk(4), &mut cache);
+    assert_eq!(tlb.access(&walk(0), &mut cache), 0);
+    assert!(tlb.access(&walk(2), &mut cache) > 0);
+    // another address space
+    tlb.access(&Walk { page: 0, satp: 2, ptes: vec![] }, &mut cache);
+    assert_eq!(tlb.stats.num_miss, 5);
+    tlb.flush();
+    assert!(tlb.access(&walk(0), &mut cache) > 0);
+}
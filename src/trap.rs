@@ -0,0 +1,94 @@
+use std::fmt::{Display, Formatter, Error};
+
+/// Synchronous exceptions. The payload is what ends up in mtval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exception {
+    InstructionAddressMisaligned(u64),
+    InstructionAccessFault(u64),
+    IllegalInstruction(u64),
+    Breakpoint(u64),
+    LoadAddressMisaligned(u64),
+    LoadAccessFault(u64),
+    StoreAddressMisaligned(u64),
+    StoreAccessFault(u64),
+    EnvironmentCallFromUMode,
+    EnvironmentCallFromSMode,
+    EnvironmentCallFromMMode,
+    InstructionPageFault(u64),
+    LoadPageFault(u64),
+    StorePageFault(u64),
+}
+
+impl Exception {
+    pub fn cause(&self) -> u64 {
+        match self {
+            Exception::InstructionAddressMisaligned(_) => 0,
+            Exception::InstructionAccessFault(_) => 1,
+            Exception::IllegalInstruction(_) => 2,
+            Exception::Breakpoint(_) => 3,
+            Exception::LoadAddressMisaligned(_) => 4,
+            Exception::LoadAccessFault(_) => 5,
+            Exception::StoreAddressMisaligned(_) => 6,
+            Exception::StoreAccessFault(_) => 7,
+            Exception::EnvironmentCallFromUMode => 8,
+            Exception::EnvironmentCallFromSMode => 9,
+            Exception::EnvironmentCallFromMMode => 11,
+            Exception::InstructionPageFault(_) => 12,
+            Exception::LoadPageFault(_) => 13,
+            Exception::StorePageFault(_) => 15,
+        }
+    }
+
+    pub fn tval(&self) -> u64 {
+        match *self {
+            Exception::InstructionAddressMisaligned(x)
+            | Exception::InstructionAccessFault(x)
+            | Exception::IllegalInstruction(x)
+            | Exception::Breakpoint(x)
+            | Exception::LoadAddressMisaligned(x)
+            | Exception::LoadAccessFault(x)
+            | Exception::StoreAddressMisaligned(x)
+            | Exception::StoreAccessFault(x)
+            | Exception::InstructionPageFault(x)
+            | Exception::LoadPageFault(x)
+            | Exception::StorePageFault(x) => x,
+            Exception::EnvironmentCallFromUMode
+            | Exception::EnvironmentCallFromSMode
+            | Exception::EnvironmentCallFromMMode => 0,
+        }
+    }
+
+    // the same memory exception reported at another address
+    pub fn with_tval(self, tval: u64) -> Self {
+        match self {
+            Exception::InstructionAccessFault(_) => Exception::InstructionAccessFault(tval),
+            Exception::LoadAddressMisaligned(_) => Exception::LoadAddressMisaligned(tval),
+            Exception::LoadAccessFault(_) => Exception::LoadAccessFault(tval),
+            Exception::StoreAddressMisaligned(_) => Exception::StoreAddressMisaligned(tval),
+            Exception::StoreAccessFault(_) => Exception::StoreAccessFault(tval),
+            e => e,
+        }
+    }
+}
+
+impl Display for Exception {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let name = match self {
+            Exception::InstructionAddressMisaligned(_) => "instruction address misaligned",
+            Exception::InstructionAccessFault(_) => "instruction access fault",
+            Exception::IllegalInstruction(_) => "illegal instruction",
+            Exception::Breakpoint(_) => "breakpoint",
+            Exception::LoadAddressMisaligned(_) => "load address misaligned",
+            Exception::LoadAccessFault(_) => "load access fault",
+            Exception::StoreAddressMisaligned(_) => "store address misaligned",
+            Exception::StoreAccessFault(_) => "store access fault",
+            Exception::EnvironmentCallFromUMode => "environment call from U-mode",
+            Exception::EnvironmentCallFromSMode => "environment call from S-mode",
+            Exception::EnvironmentCallFromMMode => "environment call from M-mode",
+            Exception::InstructionPageFault(_) => "instruction page fault",
+            Exception::LoadPageFault(_) => "load page fault",
+            Exception::StorePageFault(_) => "store page fault",
+        };
+        write!(f, "{} (tval {:x})", name, self.tval())
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::device::{Device, Lines};
+
+// registers, DLL and DLM replace RBR/THR and IER while LCR.DLAB is set
+const RBR: u64 = 0;
+const IER: u64 = 1;
+const IIR: u64 = 2;
+const LCR: u64 = 3;
+const MCR: u64 = 4;
+const LSR: u64 = 5;
+const MSR: u64 = 6;
+const SCR: u64 = 7;
+
+const IER_RDI: u8 = 1 << 0;
+const IER_THRI: u8 = 1 << 1;
+const LCR_DLAB: u8 = 1 << 7;
+const LSR_DR: u8 = 1 << 0;
+const LSR_THRE: u8 = 1 << 5;
+const LSR_TEMT: u8 = 1 << 6;
+// the low bit is set when nothing is pending, 0xc0 says the FIFOs are on
+const IIR_NONE: u8 = 0xc1;
+const IIR_THRI: u8 = 0xc2;
+const IIR_RDI: u8 = 0xc4;
+
+/// A 16550 that transmits at once. Input is read on a thread of its own,
+/// started on first use, so that polling never blocks the simulation.
+pub struct Uart {
+    input: Option<Box<dyn Read + Send>>,
+    received: Option<Receiver<u8>>,
+    rx: VecDeque<u8>,
+    output: Box<dyn Write>,
+    ier: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    divisor: u16,
+    // THR empty is reported once per transmit, until IIR is read
+    thr_interrupt: bool,
+    irq: Lines,
+    line: u64,
+}
+
+impl Uart {
+    pub fn new(input: Box<dyn Read + Send>, output: Box<dyn Write>, irq: Lines, line: u64) -> Self {
+        Self {
+            input: Some(input),
+            received: None,
+            rx: VecDeque::new(),
+            output,
+            ier: 0,
+            lcr: 0,
+            mcr: 0,
+            scr: 0,
+            divisor: 0,
+            thr_interrupt: false,
+            irq,
+            line,
+        }
+    }
+
+    // moves whatever the input thread has read into the receive FIFO
+    fn poll(&mut self) {
+        if let Some(mut input) = self.input.take() {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut byte = [0];
+                while input.read_exact(&mut byte).is_ok() && sender.send(byte[0]).is_ok() {}
+            });
+            self.received = Some(receiver);
+        }
+        if let Some(received) = &self.received {
+            self.rx.extend(received.try_iter());
+        }
+    }
+
+    fn interrupt(&self) -> u8 {
+        if self.ier & IER_RDI != 0 && !self.rx.is_empty() {
+            IIR_RDI
+        } else if self.ier & IER_THRI != 0 && self.thr_interrupt {
+            IIR_THRI
+        } else {
+            IIR_NONE
+        }
+    }
+}
+
+impl Device for Uart {
+    fn read(&mut self, offset: u64, size: u64) -> Option<u64> {
+        if size != 1 {
+            return None;
+        }
+        let dlab = self.lcr & LCR_DLAB != 0;
+        let value = match offset {
+            RBR if dlab => self.divisor as u8,
+            IER if dlab => (self.divisor >> 8) as u8,
+            RBR => {
+                self.poll();
+                self.rx.pop_front().unwrap_or(0)
+            }
+            IER => self.ier,
+            IIR => {
+                let iir = self.interrupt();
+                if iir == IIR_THRI {
+                    self.thr_interrupt = false;
+                }
+                iir
+            }
+            LCR => self.lcr,
+            MCR => self.mcr,
+            LSR => {
+                self.poll();
+                LSR_THRE | LSR_TEMT | if self.rx.is_empty() { 0 } else { LSR_DR }
+            }
+            MSR => 0,
+            SCR => self.scr,
+            _ => return None,
+        };
+        Some(value as u64)
+    }
+
+    fn write(&mut self, offset: u64, size: u64, value: u64) -> bool {
+        if size != 1 {
+            return false;
+        }
+        let value = value as u8;
+        let dlab = self.lcr & LCR_DLAB != 0;
+        match offset {
+            RBR if dlab => self.divisor = (self.divisor & 0xff00) | value as u16,
+            IER if dlab => self.divisor = (self.divisor & 0xff) | (value as u16) << 8,
+            RBR => {
+                let _ = self.output.write_all(&[value]);
+                let _ = self.output.flush();
+                self.thr_interrupt = true;
+            }
+            IER => {
+                // enabling it reports the empty THR right away
+                if value & IER_THRI != 0 && self.ier & IER_THRI == 0 {
+                    self.thr_interrupt = true;
+                }
+                self.ier = value & 0xf;
+            }
+            // FCR, the FIFOs are always on
+            IIR => {}
+            LCR => self.lcr = value,
+            MCR => self.mcr = value & 0x1f,
+            LSR | MSR => {}
+            SCR => self.scr = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn tick(&mut self, _now: u64) {
+        if self.ier & IER_RDI != 0 {
+            self.poll();
+        }
+        self.irq.set(self.line, self.interrupt() != IIR_NONE);
+    }
+}